@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+
+/// The entity the camera follows, there should only be one
+#[derive(Component)]
+pub struct CameraTarget;
+
+#[derive(Component, Clone)]
+pub struct CameraFollow {
+    /// Roughly how many seconds the camera takes to catch up with its
+    /// target, the movement is critically damped so it never overshoots
+    pub smooth_time: f32,
+    /// Seconds of the target's movement the camera looks ahead by, `None`
+    /// to keep the target in the middle
+    pub look_ahead: Option<f32>,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    /// Change in zoom per line scrolled
+    pub zoom_step: f32,
+    /// Current velocity of the camera, kept between frames for the smoothing
+    pub velocity: Vec2,
+    /// Where the target was last frame, to tell how fast it is moving
+    pub last_target: Option<Vec2>,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        return Self {
+            smooth_time: 0.3,
+            look_ahead: Some(0.4),
+            min_zoom: 0.25,
+            max_zoom: 1.,
+            zoom_step: 0.1,
+            velocity: Vec2::ZERO,
+            last_target: None,
+        };
+    }
+}
@@ -0,0 +1,22 @@
+use bevy::prelude::*;
+use systems::{follow_camera_target, spawn_camera, zoom_camera};
+
+pub mod components;
+pub mod systems;
+
+/// A camera that follows the entity with a
+/// [`CameraTarget`](components::CameraTarget) and never shows anything
+/// outside the map. Needs the [`TerrainPlugin`](crate::terrain::TerrainPlugin)
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, spawn_camera)
+            .add_systems(Update, zoom_camera)
+            // after everything has moved this frame, but before it is drawn
+            .add_systems(
+                PostUpdate,
+                follow_camera_target.before(TransformSystem::TransformPropagate),
+            );
+    }
+}
@@ -0,0 +1,128 @@
+use bevy::{
+    input::mouse::{MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::terrain::coords::TerrainCoords;
+
+use super::components::{CameraFollow, CameraTarget};
+
+/// Lines scrolled by one pixel of precise scrolling, such as on a touchpad
+const LINES_PER_PIXEL: f32 = 1. / 20.;
+
+pub fn spawn_camera(mut commands: Commands, coords: Res<TerrainCoords>) {
+    let follow = CameraFollow::default();
+
+    commands.spawn((
+        Camera2d,
+        Projection::Orthographic(OrthographicProjection {
+            scale: follow.max_zoom / 2.,
+            ..OrthographicProjection::default_2d()
+        }),
+        Transform::from_translation(coords.bounds().center().extend(0.)),
+        follow,
+    ));
+}
+
+/// Scrolling up zooms in
+pub fn zoom_camera(
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mut q_camera: Query<(&mut Projection, &CameraFollow)>,
+) {
+    let lines: f32 = mouse_wheel
+        .read()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y * LINES_PER_PIXEL,
+        })
+        .sum();
+    if lines == 0. {
+        return;
+    }
+
+    for (mut projection, follow) in q_camera.iter_mut() {
+        let Projection::Orthographic(orthographic) = projection.as_mut() else {
+            continue;
+        };
+
+        orthographic.scale = (orthographic.scale * (1. - follow.zoom_step).powf(lines))
+            .clamp(follow.min_zoom, follow.max_zoom);
+    }
+}
+
+pub fn follow_camera_target(
+    q_target: Query<&Transform, (With<CameraTarget>, Without<CameraFollow>)>,
+    mut q_camera: Query<(&mut Transform, &mut CameraFollow, &Projection)>,
+    coords: Res<TerrainCoords>,
+    time: Res<Time>,
+) {
+    let Ok(target) = q_target.single() else {
+        return;
+    };
+    let target = target.translation.truncate();
+    let delta = time.delta_secs();
+
+    for (mut transform, mut follow, projection) in q_camera.iter_mut() {
+        let target_velocity = match follow.last_target {
+            Some(last_target) if delta > 0. => (target - last_target) / delta,
+            _ => Vec2::ZERO,
+        };
+        follow.last_target = Some(target);
+
+        let goal = target + target_velocity * follow.look_ahead.unwrap_or(0.);
+        let (position, velocity) = smooth_damp(
+            transform.translation.truncate(),
+            goal,
+            follow.velocity,
+            follow.smooth_time,
+            delta,
+        );
+        follow.velocity = velocity;
+
+        let half_view = match projection {
+            Projection::Orthographic(orthographic) => orthographic.area.half_size(),
+            _ => Vec2::ZERO,
+        };
+        let position = clamp_to_bounds(position, half_view, coords.bounds());
+
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+/// Critically damped spring towards `target`, from Game Programming Gems 4
+/// chapter 1.10. Returns the new position and velocity
+fn smooth_damp(
+    current: Vec2,
+    target: Vec2,
+    velocity: Vec2,
+    smooth_time: f32,
+    delta: f32,
+) -> (Vec2, Vec2) {
+    let omega = 2. / smooth_time.max(f32::EPSILON);
+    let x = omega * delta;
+    let decay = 1. / (1. + x + 0.48 * x * x + 0.235 * x * x * x);
+
+    let change = current - target;
+    let temp = (velocity + omega * change) * delta;
+
+    return (
+        target + (change + temp) * decay,
+        (velocity - omega * temp) * decay,
+    );
+}
+
+/// Keeps a view of `half_view` around `position` inside `bounds`. On an axis
+/// where the view is bigger than the bounds they are centered instead
+fn clamp_to_bounds(position: Vec2, half_view: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |position: f32, half_view: f32, min: f32, max: f32| -> f32 {
+        if max - min <= half_view * 2. {
+            return (min + max) / 2.;
+        }
+        return position.clamp(min + half_view, max - half_view);
+    };
+
+    return Vec2::new(
+        clamp_axis(position.x, half_view.x, bounds.min.x, bounds.max.x),
+        clamp_axis(position.y, half_view.y, bounds.min.y, bounds.max.y),
+    );
+}
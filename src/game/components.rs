@@ -0,0 +1,12 @@
+use bevy::prelude::*;
+
+#[derive(Component)]
+pub struct HudText;
+
+/// Tops up the oxygen and battery of a submarine inside its radius
+#[derive(Component, Clone)]
+pub struct RefillStation {
+    pub radius: f32,
+    /// Oxygen and charge added per second
+    pub rate: f32,
+}
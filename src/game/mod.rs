@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use state::GameState;
+use systems::{
+    despawn_refill_stations, refill_at_stations, show_game_over, spawn_hud, spawn_refill_stations,
+    update_hud,
+};
+
+use crate::terrain::events::MapLoaded;
+
+pub mod components;
+pub mod state;
+pub mod systems;
+
+pub const REFILL_STATION_COLOR: Color = Color::hsl(160.0, 0.7, 0.5);
+
+/// The rules of a dive and the UI around it. Needs the
+/// [`TerrainPlugin`](crate::terrain::TerrainPlugin)
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(Startup, spawn_refill_stations)
+            // a loaded map has its own refill points
+            .add_systems(
+                Update,
+                despawn_refill_stations.run_if(on_event::<MapLoaded>),
+            )
+            .add_systems(
+                Update,
+                spawn_refill_stations
+                    .after(despawn_refill_stations)
+                    .run_if(on_event::<MapLoaded>),
+            )
+            .add_systems(Update, update_hud)
+            .add_systems(
+                Update,
+                refill_at_stations.run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::GameOver), show_game_over);
+    }
+}
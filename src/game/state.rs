@@ -0,0 +1,27 @@
+use bevy::prelude::*;
+
+#[derive(States, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum GameState {
+    #[default]
+    Playing,
+    GameOver,
+}
+
+/// Why the dive ended, inserted along with the switch to
+/// [`GameState::GameOver`]
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GameOverReason {
+    HullBreached,
+    OutOfOxygen,
+    OutOfPower,
+}
+
+impl GameOverReason {
+    pub fn message(self) -> &'static str {
+        return match self {
+            GameOverReason::HullBreached => "The hull gave way",
+            GameOverReason::OutOfOxygen => "The oxygen ran out",
+            GameOverReason::OutOfPower => "The battery ran flat",
+        };
+    }
+}
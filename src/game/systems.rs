@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+
+use crate::{
+    lighting::components::LightSource,
+    submarine::components::{Battery, Hull, Oxygen, Submarine},
+    terrain::{coords::TerrainCoords, resources::Map},
+};
+
+use super::{
+    components::{HudText, RefillStation},
+    state::GameOverReason,
+    REFILL_STATION_COLOR,
+};
+
+const HUD_FONT_SIZE: f32 = 18.;
+const GAME_OVER_FONT_SIZE: f32 = 40.;
+const REFILL_RADIUS: f32 = 25.;
+const REFILL_RATE: f32 = 25.;
+
+pub fn spawn_refill_stations(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+) {
+    let mesh = meshes.add(Annulus::new(REFILL_RADIUS - 2., REFILL_RADIUS));
+    let material = materials.add(REFILL_STATION_COLOR);
+
+    for &cell in map.refill_points.iter() {
+        commands.spawn((
+            RefillStation {
+                radius: REFILL_RADIUS,
+                rate: REFILL_RATE,
+            },
+            // the stations glow, so they can be found in the dark
+            LightSource {
+                color: REFILL_STATION_COLOR,
+                radius: 60.,
+                intensity: 0.3,
+                cone: None,
+            },
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material.clone()),
+            // above the terrain, below the submarine
+            Transform::from_translation(coords.cell_to_world(cell).extend(4.)),
+        ));
+    }
+}
+
+pub fn despawn_refill_stations(
+    mut commands: Commands,
+    q_stations: Query<Entity, With<RefillStation>>,
+) {
+    for station in q_stations.iter() {
+        commands.entity(station).despawn();
+    }
+}
+
+pub fn refill_at_stations(
+    q_stations: Query<(&Transform, &RefillStation)>,
+    mut q_submarine: Query<(&Transform, &mut Oxygen, &mut Battery), With<Submarine>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+    time: Res<Time>,
+) {
+    for (submarine_transform, mut oxygen, mut battery) in q_submarine.iter_mut() {
+        let position = submarine_transform.translation.truncate();
+
+        for (station_transform, station) in q_stations.iter() {
+            let station_position = station_transform.translation.truncate();
+            if station_position.distance(position) > station.radius {
+                continue;
+            }
+            // no refilling through a wall
+            let in_sight = map.line_of_sight(
+                coords.world_to_cell_position(station_position),
+                coords.world_to_cell_position(position),
+            );
+            if !in_sight {
+                continue;
+            }
+
+            let refill = station.rate * time.delta_secs();
+            oxygen.amount = (oxygen.amount + refill).min(oxygen.capacity);
+            battery.charge = (battery.charge + refill).min(battery.capacity);
+        }
+    }
+}
+
+pub fn spawn_hud(mut commands: Commands) {
+    commands.spawn((
+        HudText,
+        Text::new(""),
+        TextFont::from_font_size(HUD_FONT_SIZE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(10.),
+            left: Val::Px(10.),
+            ..default()
+        },
+    ));
+}
+
+pub fn update_hud(
+    q_submarine: Query<(&Hull, &Oxygen, &Battery), With<Submarine>>,
+    mut q_text: Query<&mut Text, With<HudText>>,
+    map: Res<Map>,
+) {
+    let Ok((hull, oxygen, battery)) = q_submarine.single() else {
+        return;
+    };
+
+    for mut text in q_text.iter_mut() {
+        text.0 = format!(
+            "Hull {:.0}%\nOxygen {:.0}%\nBattery {:.0}%\nSeed {}",
+            hull.fraction() * 100.,
+            oxygen.amount / oxygen.capacity * 100.,
+            battery.charge / battery.capacity * 100.,
+            map.seed,
+        );
+    }
+}
+
+pub fn show_game_over(mut commands: Commands, reason: Option<Res<GameOverReason>>) {
+    let message = reason.map_or("", |reason| reason.message());
+
+    commands
+        .spawn(Node {
+            width: Val::Percent(100.),
+            height: Val::Percent(100.),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        })
+        .with_child((
+            Text::new(format!("Game over\n{message}")),
+            TextFont::from_font_size(GAME_OVER_FONT_SIZE),
+            TextLayout::new_with_justify(JustifyText::Center),
+        ));
+}
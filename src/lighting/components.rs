@@ -0,0 +1,24 @@
+use bevy::prelude::*;
+
+/// Lights up the cave around an entity. The light fades out towards its
+/// radius and stops at the walls
+#[derive(Component, Clone)]
+pub struct LightSource {
+    pub color: Color,
+    /// World distance the light reaches
+    pub radius: f32,
+    /// Opacity of the light right at the source, from 0 to 1
+    pub intensity: f32,
+    /// Radians on either side of the entity's local x axis that are lit,
+    /// `None` to light all the way around
+    pub cone: Option<f32>,
+}
+
+/// The mesh drawing a [`LightSource`], kept separate so it is not rotated
+/// along with the source
+#[derive(Component)]
+pub struct LightMesh {
+    pub source: Entity,
+    /// Set when the source moved or the walls around it changed
+    pub needs_rebuild: bool,
+}
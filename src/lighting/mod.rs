@@ -0,0 +1,25 @@
+use bevy::prelude::*;
+use resources::LightMaterial;
+use systems::{mark_lights_for_rebuild, spawn_light_meshes, update_light_meshes};
+
+pub mod components;
+pub mod resources;
+pub mod systems;
+
+/// Draws every [`LightSource`](components::LightSource) on top of the dim
+/// cave, with the terrain casting shadows. Needs the
+/// [`TerrainPlugin`](crate::terrain::TerrainPlugin)
+pub struct LightingPlugin;
+
+impl Plugin for LightingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightMaterial>()
+            .add_systems(Update, spawn_light_meshes)
+            // after everything has moved and the terrain was edited this frame
+            .add_systems(PostUpdate, mark_lights_for_rebuild)
+            .add_systems(
+                PostUpdate,
+                update_light_meshes.after(mark_lights_for_rebuild),
+            );
+    }
+}
@@ -0,0 +1,17 @@
+use bevy::prelude::*;
+
+/// The material every light mesh is drawn with, their color and falloff
+/// come from their vertices
+#[derive(Resource, Clone)]
+pub struct LightMaterial {
+    pub handle: Handle<ColorMaterial>,
+}
+
+impl FromWorld for LightMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<ColorMaterial>>();
+        return Self {
+            handle: materials.add(Color::WHITE),
+        };
+    }
+}
@@ -0,0 +1,152 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use crate::terrain::{
+    chunk::Chunk,
+    coords::TerrainCoords,
+    events::{MapLoaded, TerrainModified},
+    resources::Map,
+    visibility::ViewCone,
+};
+
+use super::{
+    components::{LightMesh, LightSource},
+    resources::LightMaterial,
+};
+
+/// Above the terrain and what is in the cave, below the fog of war
+const LIGHT_Z: f32 = 4.2;
+
+pub fn spawn_light_meshes(
+    mut commands: Commands,
+    q_sources: Query<Entity, Added<LightSource>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    light_material: Res<LightMaterial>,
+) {
+    for source in q_sources.iter() {
+        commands.spawn((
+            LightMesh {
+                source,
+                needs_rebuild: true,
+            },
+            Mesh2d(meshes.add(Chunk::empty_mesh())),
+            MeshMaterial2d(light_material.handle.clone()),
+            Transform::from_xyz(0., 0., LIGHT_Z),
+        ));
+    }
+}
+
+/// Lights only have to be rebuilt when their source moved or changed, or
+/// when the walls they reach were edited
+pub fn mark_lights_for_rebuild(
+    q_sources: Query<(Ref<Transform>, Ref<LightSource>)>,
+    mut q_light_meshes: Query<&mut LightMesh>,
+    mut terrain_modified: EventReader<TerrainModified>,
+    mut map_loaded: EventReader<MapLoaded>,
+    coords: Res<TerrainCoords>,
+) {
+    let changed_cells: Vec<Vec2> = terrain_modified
+        .read()
+        .flat_map(|modified| modified.changes.iter())
+        .map(|change| coords.cell_to_world(change.cell))
+        .collect();
+    let map_replaced = map_loaded.read().count() > 0;
+
+    for mut light_mesh in q_light_meshes.iter_mut() {
+        let Ok((transform, light)) = q_sources.get(light_mesh.source) else {
+            continue;
+        };
+
+        let origin = transform.translation.truncate();
+        // a cell changes the walls up to a square away from it
+        let reach = light.radius + coords.square_size;
+        let walls_changed = changed_cells
+            .iter()
+            .any(|cell| cell.distance(origin) <= reach);
+
+        if transform.is_changed() || light.is_changed() || walls_changed || map_replaced {
+            light_mesh.needs_rebuild = true;
+        }
+    }
+}
+
+/// Rebuilds the light of every source marked by [`mark_lights_for_rebuild`]
+/// from what it can see of the cave
+pub fn update_light_meshes(
+    mut commands: Commands,
+    q_sources: Query<(&Transform, &LightSource)>,
+    mut q_light_meshes: Query<(Entity, &mut LightMesh, &Mesh2d)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+) {
+    for (entity, mut light_mesh, mesh_handle) in q_light_meshes.iter_mut() {
+        let Ok((transform, light)) = q_sources.get(light_mesh.source) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+        if !light_mesh.needs_rebuild {
+            continue;
+        }
+        let Some(mesh) = meshes.get_mut(&mesh_handle.0) else {
+            continue;
+        };
+        light_mesh.needs_rebuild = false;
+
+        let origin = transform.translation.truncate();
+        let cone = light.cone.map(|half_angle| ViewCone {
+            direction: transform.right().truncate(),
+            half_angle,
+        });
+
+        let outline: Vec<Vec2> = map
+            .visibility_polygon(
+                coords.world_to_cell_position(origin),
+                light.radius / coords.square_size,
+                cone,
+            )
+            .into_iter()
+            .map(|point| coords.cell_position_to_world(point))
+            .collect();
+
+        *mesh = build_light_mesh(origin, &outline, light, cone.is_none());
+    }
+}
+
+/// A fan from `origin` through `outline`, in world space
+fn build_light_mesh(origin: Vec2, outline: &[Vec2], light: &LightSource, closed: bool) -> Mesh {
+    let brightness = |point: Vec2| -> [f32; 4] {
+        let falloff = 1. - point.distance(origin) / light.radius;
+        return light
+            .color
+            .with_alpha(light.intensity * falloff.max(0.))
+            .to_linear()
+            .to_f32_array();
+    };
+
+    let mut positions = vec![origin.extend(0.).to_array()];
+    let mut colors = vec![brightness(origin)];
+    for point in outline.iter() {
+        positions.push(point.extend(0.).to_array());
+        colors.push(brightness(*point));
+    }
+
+    let count = outline.len() as u32;
+    let mut indices = Vec::new();
+    for index in 1..count {
+        indices.extend([0, index, index + 1]);
+    }
+    if closed && count > 1 {
+        indices.extend([0, count, 1]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_indices(Indices::U32(indices));
+
+    return mesh;
+}
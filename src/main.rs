@@ -1,25 +1,54 @@
 use bevy::prelude::*;
-use terrain::{chunk::CHUNK_SIZE, TerrainPlugin, SQUARE_SIZE, WALL_COLOR};
+use camera::CameraPlugin;
+use game::GamePlugin;
+use lighting::LightingPlugin;
+use submarine::SubmarinePlugin;
+use terrain::{
+    generators::generator_from_name, resources::TerrainConfig, tile::Tile, TerrainPlugin,
+};
 
+mod camera;
+mod game;
+mod lighting;
+mod submarine;
 mod terrain;
 
 fn main() {
-    App::new()
-        .insert_resource(ClearColor(WALL_COLOR))
-        .add_plugins((DefaultPlugins,))
-        .add_plugins(TerrainPlugin)
-        .add_systems(Startup, setup)
+    let mut app = App::new();
+    app.add_plugins((DefaultPlugins,));
+
+    // read after the log plugin is set up, so bad arguments are reported
+    let terrain_config = terrain_config_from_args();
+
+    app.insert_resource(ClearColor(terrain_config.tile_color(Tile::Bedrock)))
+        .add_plugins(TerrainPlugin::new(terrain_config))
+        .add_plugins(GamePlugin)
+        .add_plugins(SubmarinePlugin)
+        .add_plugins(CameraPlugin)
+        .add_plugins(LightingPlugin)
         .run();
 }
 
-fn setup(mut commands: Commands) {
-    // in the future camera pos should follow plyar, so main won't know it
-    commands.spawn((
-        Camera2d::default(),
-        Transform::from_xyz(
-            7. * CHUNK_SIZE as f32 * SQUARE_SIZE / 2. + SQUARE_SIZE,
-            3. * CHUNK_SIZE as f32 * SQUARE_SIZE / 2. + SQUARE_SIZE,
-            0.,
-        ),
-    ));
+/// `cargo run -- [generator] [seed]`, so a reported cave can be reproduced
+fn terrain_config_from_args() -> TerrainConfig {
+    let mut args = std::env::args().skip(1);
+    let mut config = TerrainConfig::default();
+
+    if let Some(name) = args.next() {
+        match generator_from_name(&name) {
+            Some(generator) => config.generator = generator,
+            None => {
+                warn!("unknown generator {name}, expected cellular, drunkard, noise, bsp or mixed")
+            }
+        }
+    }
+
+    if let Some(seed) = args.next() {
+        match seed.parse() {
+            Ok(seed) => config.seed = Some(seed),
+            Err(_) => warn!("seed {seed} is not a valid u64"),
+        }
+    }
+
+    return config;
 }
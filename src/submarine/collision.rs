@@ -0,0 +1,151 @@
+use bevy::prelude::*;
+
+use crate::terrain::{coords::TerrainCoords, resources::Map, tile::Tile};
+
+use super::{
+    components::{Collider, Velocity},
+    events::TerrainImpact,
+};
+
+/// Times the deepest contact is pushed out each frame, enough to settle into
+/// the corners of the cave
+const MAX_ITERATIONS: usize = 4;
+/// Slower impacts than this only slide along the wall without an event
+const MIN_IMPACT_SPEED: f32 = 1.;
+
+struct Contact {
+    point: Vec2,
+    normal: Vec2,
+    depth: f32,
+}
+
+/// Pushes colliders out of the walls and removes the part of their
+/// velocity going into them, so they slide along the contour
+pub fn collide_with_terrain(
+    mut q_bodies: Query<(Entity, &mut Transform, &mut Velocity, &Collider)>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+    mut terrain_impacts: EventWriter<TerrainImpact>,
+) {
+    for (entity, mut transform, mut velocity, collider) in q_bodies.iter_mut() {
+        let mut impact: Option<TerrainImpact> = None;
+
+        for _ in 0..MAX_ITERATIONS {
+            let Some(contact) = deepest_contact(&transform, collider, &map, &coords) else {
+                break;
+            };
+
+            transform.translation += (contact.normal * contact.depth).extend(0.);
+
+            let speed = -velocity.linear.dot(contact.normal);
+            if speed <= 0. {
+                continue;
+            }
+            velocity.linear += contact.normal * speed;
+
+            if speed >= MIN_IMPACT_SPEED && impact.is_none_or(|impact| speed > impact.speed) {
+                impact = Some(TerrainImpact {
+                    entity,
+                    point: contact.point,
+                    normal: contact.normal,
+                    speed,
+                    tile: map
+                        .solid_cell_near(coords.world_to_cell_position(contact.point))
+                        .map_or(Tile::Bedrock, |(x, y)| map.points[x][y]),
+                });
+            }
+        }
+
+        if let Some(impact) = impact {
+            terrain_impacts.write(impact);
+        }
+    }
+}
+
+fn deepest_contact(
+    transform: &Transform,
+    collider: &Collider,
+    map: &Map,
+    coords: &TerrainCoords,
+) -> Option<Contact> {
+    let center = transform.translation.truncate();
+    let spine = transform.right().truncate() * collider.half_length;
+    let (spine_start, spine_end) = (center - spine, center + spine);
+
+    let reach = Vec2::splat(collider.radius + collider.half_length);
+    let segments = map.contour_segments_in(
+        coords.world_to_cell_position(center - reach),
+        coords.world_to_cell_position(center + reach),
+    );
+
+    let mut deepest: Option<Contact> = None;
+    for segment in segments {
+        let [wall_start, wall_end] = segment.map(|point| coords.cell_position_to_world(point));
+        let (s, t) = closest_points(spine_start, spine_end, wall_start, wall_end);
+        let on_spine = spine_start.lerp(spine_end, s);
+        let on_wall = wall_start.lerp(wall_end, t);
+
+        // segments have the wall on their left
+        let wall_normal = -(wall_end - wall_start).perp().normalize_or_zero();
+        let offset = on_spine - on_wall;
+        let separation = offset.length();
+
+        // only the middle of a segment can tell a capsule behind it apart
+        // from one that is past the end, next to a corner
+        let behind = t > 0. && t < 1. && offset.dot(wall_normal) < 0.;
+        let (normal, depth) = if behind {
+            (wall_normal, collider.radius + separation)
+        } else if separation > f32::EPSILON {
+            (offset / separation, collider.radius - separation)
+        } else {
+            (wall_normal, collider.radius)
+        };
+
+        if depth > 0. && deepest.as_ref().is_none_or(|deepest| depth > deepest.depth) {
+            deepest = Some(Contact {
+                point: on_wall,
+                normal,
+                depth,
+            });
+        }
+    }
+
+    return deepest;
+}
+
+/// Where along the segments from `p1` to `q1` and from `p2` to `q2` the
+/// closest points between them are, from Real-Time Collision Detection 5.1.9
+fn closest_points(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> (f32, f32) {
+    let (d1, d2, r) = (q1 - p1, q2 - p2, p1 - p2);
+    let (a, e, f) = (d1.length_squared(), d2.length_squared(), d2.dot(r));
+
+    if a <= f32::EPSILON && e <= f32::EPSILON {
+        return (0., 0.);
+    }
+    if a <= f32::EPSILON {
+        return (0., (f / e).clamp(0., 1.));
+    }
+
+    let c = d1.dot(r);
+    if e <= f32::EPSILON {
+        return ((-c / a).clamp(0., 1.), 0.);
+    }
+
+    let b = d1.dot(d2);
+    let denominator = a * e - b * b;
+    // parallel segments can use any point, so start from the first one
+    let s = if denominator > f32::EPSILON {
+        ((b * f - c * e) / denominator).clamp(0., 1.)
+    } else {
+        0.
+    };
+
+    let t = (b * s + f) / e;
+    if t < 0. {
+        return ((-c / a).clamp(0., 1.), 0.);
+    }
+    if t > 1. {
+        return (((b - c) / a).clamp(0., 1.), 1.);
+    }
+    return (s, t);
+}
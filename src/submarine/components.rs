@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+
+#[derive(Component)]
+pub struct Submarine;
+
+/// How the submarine handles. Forces are accelerations, the submarine has
+/// no mass of its own
+#[derive(Component, Clone)]
+pub struct SubmarineMovement {
+    pub thrust: f32,
+    pub reverse_thrust: f32,
+    /// Angular acceleration in radians per second squared
+    pub torque: f32,
+    /// Fraction of the velocity lost per second to the water
+    pub linear_drag: f32,
+    pub angular_drag: f32,
+    /// Upward force as a fraction of gravity, 1 for neutral buoyancy
+    pub buoyancy: f32,
+}
+
+impl Default for SubmarineMovement {
+    fn default() -> Self {
+        return Self {
+            thrust: 120.,
+            reverse_thrust: 60.,
+            torque: 12.,
+            linear_drag: 1.2,
+            angular_drag: 4.,
+            buoyancy: 1.,
+        };
+    }
+}
+
+/// What the pilot is asking for, each from -1 to 1
+#[derive(Component, Default, Clone, Copy)]
+pub struct Throttle {
+    /// Positive is forward
+    pub forward: f32,
+    /// Positive is counterclockwise
+    pub turn: f32,
+}
+
+#[derive(Component, Default, Clone, Copy)]
+pub struct Velocity {
+    pub linear: Vec2,
+    /// Radians per second, positive is counterclockwise
+    pub angular: f32,
+}
+
+/// A capsule along the entity's local x axis that is kept out of the
+/// terrain, a circle when `half_length` is 0
+#[derive(Component, Clone, Copy)]
+pub struct Collider {
+    pub radius: f32,
+    /// Distance from the middle to the center of either end
+    pub half_length: f32,
+}
+
+/// Mines the terrain in a rectangle ahead of the nose while the drill key
+/// is held
+#[derive(Component, Clone)]
+pub struct Drill {
+    /// Dig power, see [`Tile::hardness`](crate::terrain::tile::Tile::hardness)
+    pub speed: f32,
+    /// How far ahead of the nose the drill reaches
+    pub reach: f32,
+    /// Width of the drilled area
+    pub width: f32,
+    /// Battery drawn per second while running
+    pub power_draw: f32,
+    /// Whether the drill is running this frame
+    pub active: bool,
+}
+
+impl Default for Drill {
+    fn default() -> Self {
+        return Self {
+            speed: 2.,
+            reach: 8.,
+            width: 10.,
+            power_draw: 2.,
+            active: false,
+        };
+    }
+}
+
+/// Hull integrity, the dive is over when it runs out
+#[derive(Component, Clone)]
+pub struct Hull {
+    pub integrity: f32,
+    pub max_integrity: f32,
+    /// Impacts slower than this do no damage
+    pub damage_threshold: f32,
+    /// Damage per unit of speed above the threshold, when hitting rock, see
+    /// [`Tile::impact_damage_scale`](crate::terrain::tile::Tile::impact_damage_scale)
+    pub damage_per_speed: f32,
+}
+
+impl Default for Hull {
+    fn default() -> Self {
+        return Self {
+            integrity: 100.,
+            max_integrity: 100.,
+            damage_threshold: 30.,
+            damage_per_speed: 0.5,
+        };
+    }
+}
+
+impl Hull {
+    pub fn fraction(&self) -> f32 {
+        return (self.integrity / self.max_integrity).clamp(0., 1.);
+    }
+}
+
+/// Breathable air, drains all the time and faster under exertion
+#[derive(Component, Clone)]
+pub struct Oxygen {
+    pub amount: f32,
+    pub capacity: f32,
+    /// Used per second just by the crew breathing
+    pub base_use: f32,
+    /// Extra used per second while thrusting or drilling
+    pub exertion_use: f32,
+}
+
+impl Default for Oxygen {
+    fn default() -> Self {
+        return Self {
+            amount: 100.,
+            capacity: 100.,
+            base_use: 0.5,
+            exertion_use: 0.5,
+        };
+    }
+}
+
+/// Power for the engine, the drill and everything else on board
+#[derive(Component, Clone)]
+pub struct Battery {
+    pub charge: f32,
+    pub capacity: f32,
+    /// Drawn per second by life support and lights
+    pub base_draw: f32,
+    /// Drawn per second at full throttle
+    pub thrust_draw: f32,
+}
+
+impl Default for Battery {
+    fn default() -> Self {
+        return Self {
+            charge: 100.,
+            capacity: 100.,
+            base_draw: 0.2,
+            thrust_draw: 0.8,
+        };
+    }
+}
+
+/// Explores the cave around the submarine
+#[derive(Component, Clone)]
+pub struct Sight {
+    /// World distance the crew can see
+    pub radius: f32,
+}
+
+impl Default for Sight {
+    fn default() -> Self {
+        return Self { radius: 50. };
+    }
+}
+
+/// Sends out a ring that lights up the walls it passes
+#[derive(Component, Clone)]
+pub struct Sonar {
+    /// World distance the ring travels before it dies out
+    pub range: f32,
+    /// World distance the ring travels per second
+    pub speed: f32,
+    /// Number of rays cast around the submarine by each ping
+    pub rays: usize,
+    /// Seconds an echo stays lit after the ring reaches it
+    pub echo_lifetime: f32,
+    /// Battery used by each ping
+    pub power_cost: f32,
+    pub cooldown: Timer,
+}
+
+impl Default for Sonar {
+    fn default() -> Self {
+        let mut cooldown = Timer::from_seconds(1.5, TimerMode::Once);
+        // ready to ping straight away
+        cooldown.set_elapsed(cooldown.duration());
+
+        return Self {
+            range: 300.,
+            speed: 200.,
+            rays: 360,
+            echo_lifetime: 2.5,
+            power_cost: 3.,
+            cooldown,
+        };
+    }
+}
+
+/// The expanding ring of one ping. Its rays were cast when it was sent, the
+/// echoes are revealed once the ring gets to them
+#[derive(Component, Clone)]
+pub struct SonarPing {
+    pub origin: Vec2,
+    pub radius: f32,
+    pub range: f32,
+    pub speed: f32,
+    pub echo_lifetime: f32,
+    /// Closest first
+    pub pending_echoes: Vec<PendingEcho>,
+}
+
+/// A wall a ping's ray hit, waiting for the ring to get there
+#[derive(Clone)]
+pub struct PendingEcho {
+    /// World distance from the ping's origin
+    pub distance: f32,
+    /// World position of the hit
+    pub point: Vec2,
+    /// The contour of the hit square in world space
+    pub segments: Vec<[Vec2; 2]>,
+}
+
+/// A piece of wall lit by a ping, fading out
+#[derive(Component, Clone)]
+pub struct SonarEcho {
+    /// The wall's contour in world space
+    pub segments: Vec<[Vec2; 2]>,
+    pub lifetime: Timer,
+}
@@ -0,0 +1,18 @@
+use bevy::prelude::*;
+
+use crate::terrain::tile::Tile;
+
+/// Sent when a [`Collider`](super::components::Collider) runs into a wall,
+/// at most once per entity per frame
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TerrainImpact {
+    pub entity: Entity,
+    /// World position of the contact
+    pub point: Vec2,
+    /// Points away from the wall
+    pub normal: Vec2,
+    /// How fast the entity was moving into the wall
+    pub speed: f32,
+    /// The wall's material
+    pub tile: Tile,
+}
@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::{game::state::GameState, terrain::events::MapLoaded};
+use collision::collide_with_terrain;
+use events::TerrainImpact;
+use sonar::{draw_sonar, expand_sonar_pings, fade_sonar_echoes, send_sonar_ping};
+use systems::{
+    damage_hull, draw_debug_casts, draw_drill, explore_surroundings, log_terrain_impacts,
+    move_submarine, read_submarine_input, return_to_spawn, run_drill, spawn_submarine,
+    use_supplies,
+};
+
+pub mod components;
+pub mod events;
+pub mod systems;
+
+pub mod collision;
+pub mod sonar;
+
+pub const SUBMARINE_COLOR: Color = Color::hsl(50.0, 0.8, 0.55);
+
+/// The player's submarine, spawned at [`Map::spawn`](crate::terrain::resources::Map::spawn).
+/// Needs the [`TerrainPlugin`](crate::terrain::TerrainPlugin) and the
+/// [`GamePlugin`](crate::game::GamePlugin) to be added first
+pub struct SubmarinePlugin;
+
+impl Plugin for SubmarinePlugin {
+    fn build(&self, app: &mut App) {
+        // the submarine stays where it was once the dive is over
+        let playing = in_state(GameState::Playing);
+
+        app.add_event::<TerrainImpact>()
+            .add_systems(Startup, spawn_submarine)
+            .add_systems(Update, read_submarine_input.run_if(playing.clone()))
+            .add_systems(
+                Update,
+                move_submarine
+                    .after(read_submarine_input)
+                    .run_if(playing.clone()),
+            )
+            .add_systems(
+                Update,
+                collide_with_terrain
+                    .after(move_submarine)
+                    .run_if(playing.clone()),
+            )
+            .add_systems(Update, log_terrain_impacts.after(collide_with_terrain))
+            .add_systems(Update, damage_hull.after(collide_with_terrain))
+            .add_systems(
+                Update,
+                run_drill
+                    .after(collide_with_terrain)
+                    .run_if(playing.clone()),
+            )
+            .add_systems(Update, draw_drill.after(run_drill).run_if(playing.clone()))
+            .add_systems(
+                Update,
+                use_supplies
+                    .after(read_submarine_input)
+                    .run_if(playing.clone()),
+            )
+            .add_systems(Update, send_sonar_ping.run_if(playing))
+            // pings already sent finish even once the dive is over
+            .add_systems(Update, expand_sonar_pings.after(send_sonar_ping))
+            .add_systems(Update, fade_sonar_echoes)
+            .add_systems(
+                Update,
+                return_to_spawn
+                    .before(move_submarine)
+                    .run_if(on_event::<MapLoaded>),
+            )
+            .add_systems(Update, explore_surroundings.after(collide_with_terrain))
+            .add_systems(Update, draw_debug_casts.after(collide_with_terrain))
+            .add_systems(
+                Update,
+                draw_sonar
+                    .after(expand_sonar_pings)
+                    .after(fade_sonar_echoes),
+            );
+    }
+}
@@ -0,0 +1,141 @@
+use std::f32::consts::TAU;
+
+use bevy::{platform::collections::HashSet, prelude::*};
+
+use crate::terrain::{coords::TerrainCoords, fog::Explorer, resources::Map};
+
+use super::components::{Battery, PendingEcho, Sonar, SonarEcho, SonarPing, Submarine};
+
+const RING_COLOR: Color = Color::srgb(0.4, 1., 0.8);
+const ECHO_COLOR: Color = Color::srgb(0.6, 1., 0.9);
+/// World distance around a hit that an echo explores
+const ECHO_EXPLORE_RADIUS: f32 = 15.;
+
+/// Q sends a ping when the sonar is ready and there is charge for it
+pub fn send_sonar_ping(
+    mut commands: Commands,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut q_sonar: Query<(&Transform, &mut Sonar, &mut Battery), With<Submarine>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+    time: Res<Time>,
+) {
+    for (transform, mut sonar, mut battery) in q_sonar.iter_mut() {
+        sonar.cooldown.tick(time.delta());
+        if !keyboard.just_pressed(KeyCode::KeyQ)
+            || !sonar.cooldown.finished()
+            || battery.charge < sonar.power_cost
+        {
+            continue;
+        }
+
+        sonar.cooldown.reset();
+        battery.charge -= sonar.power_cost;
+
+        let origin = transform.translation.truncate();
+        commands.spawn(SonarPing {
+            origin,
+            radius: 0.,
+            range: sonar.range,
+            speed: sonar.speed,
+            echo_lifetime: sonar.echo_lifetime,
+            pending_echoes: cast_sonar_rays(origin, &sonar, &map, &coords),
+        });
+    }
+}
+
+/// The contour of every square hit by a ray, each square once
+fn cast_sonar_rays(
+    origin: Vec2,
+    sonar: &Sonar,
+    map: &Map,
+    coords: &TerrainCoords,
+) -> Vec<PendingEcho> {
+    let cell_origin = coords.world_to_cell_position(origin);
+    let range = sonar.range / coords.square_size;
+
+    let mut hit_squares = HashSet::new();
+    let mut echoes = Vec::new();
+
+    for ray in 0..sonar.rays {
+        let direction = Vec2::from_angle(ray as f32 / sonar.rays as f32 * TAU);
+        let Some(hit) = map.raycast(cell_origin, direction, range) else {
+            continue;
+        };
+        if !hit_squares.insert(hit.square) {
+            continue;
+        }
+
+        let segments = map
+            .square_contour(hit.square)
+            .into_iter()
+            .map(|segment| segment.map(|point| coords.cell_position_to_world(point)))
+            .collect();
+        echoes.push(PendingEcho {
+            distance: hit.distance * coords.square_size,
+            point: coords.cell_position_to_world(hit.point),
+            segments,
+        });
+    }
+
+    echoes.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    return echoes;
+}
+
+/// Grows the rings and turns the hits they reach into echoes, exploring
+/// the walls they light up
+pub fn expand_sonar_pings(
+    mut commands: Commands,
+    mut q_pings: Query<(Entity, &mut SonarPing)>,
+    mut explorer: Explorer,
+    time: Res<Time>,
+) {
+    for (entity, mut ping) in q_pings.iter_mut() {
+        ping.radius += ping.speed * time.delta_secs();
+
+        let reached = ping
+            .pending_echoes
+            .iter()
+            .take_while(|echo| echo.distance <= ping.radius)
+            .count();
+        let lifetime = ping.echo_lifetime;
+        for echo in ping.pending_echoes.drain(..reached) {
+            explorer.reveal_circle(echo.point, ECHO_EXPLORE_RADIUS);
+            commands.spawn(SonarEcho {
+                segments: echo.segments,
+                lifetime: Timer::from_seconds(lifetime, TimerMode::Once),
+            });
+        }
+
+        if ping.radius >= ping.range {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn fade_sonar_echoes(
+    mut commands: Commands,
+    mut q_echoes: Query<(Entity, &mut SonarEcho)>,
+    time: Res<Time>,
+) {
+    for (entity, mut echo) in q_echoes.iter_mut() {
+        echo.lifetime.tick(time.delta());
+        if echo.lifetime.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn draw_sonar(q_pings: Query<&SonarPing>, q_echoes: Query<&SonarEcho>, mut gizmos: Gizmos) {
+    for ping in q_pings.iter() {
+        let alpha = 1. - ping.radius / ping.range;
+        gizmos.circle_2d(ping.origin, ping.radius, RING_COLOR.with_alpha(alpha));
+    }
+
+    for echo in q_echoes.iter() {
+        let color = ECHO_COLOR.with_alpha(echo.lifetime.fraction_remaining());
+        for [a, b] in echo.segments.iter() {
+            gizmos.line_2d(*a, *b, color);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::prelude::*;
+
+use crate::{
+    camera::components::CameraTarget,
+    game::state::{GameOverReason, GameState},
+    lighting::components::LightSource,
+    terrain::{
+        coords::TerrainCoords, editor::TerrainEditor, events::TerrainChangeCause, fog::Explorer,
+        resources::Map,
+    },
+};
+
+use super::{
+    components::{
+        Battery, Collider, Drill, Hull, Oxygen, Sight, Sonar, Submarine, SubmarineMovement,
+        Throttle, Velocity,
+    },
+    events::TerrainImpact,
+    SUBMARINE_COLOR,
+};
+
+/// Downward acceleration cancelled out by [`SubmarineMovement::buoyancy`]
+const GRAVITY: f32 = 60.;
+const SUBMARINE_RADIUS: f32 = 5.;
+const SUBMARINE_LENGTH: f32 = 10.;
+const DRILL_COLOR: Color = Color::srgb(1., 0.4, 0.1);
+const HEADLIGHT_COLOR: Color = Color::srgb(1., 0.95, 0.8);
+const DEBUG_CAST_COLOR: Color = Color::srgb(1., 0.2, 0.6);
+/// World distance the debug casts look ahead
+const DEBUG_CAST_DISTANCE: f32 = 200.;
+
+pub fn spawn_submarine(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+) {
+    // the capsule is built along y, the submarine faces along its local x
+    let hull = Mesh::from(Capsule2d::new(SUBMARINE_RADIUS, SUBMARINE_LENGTH))
+        .rotated_by(Quat::from_rotation_z(FRAC_PI_2));
+
+    commands.spawn((
+        Submarine,
+        CameraTarget,
+        SubmarineMovement::default(),
+        Throttle::default(),
+        Velocity::default(),
+        Drill::default(),
+        // what it takes to stay alive down there
+        (Hull::default(), Oxygen::default(), Battery::default()),
+        Sonar::default(),
+        Sight::default(),
+        LightSource {
+            color: HEADLIGHT_COLOR,
+            radius: 160.,
+            intensity: 0.45,
+            cone: Some(0.45),
+        },
+        Collider {
+            radius: SUBMARINE_RADIUS,
+            half_length: SUBMARINE_LENGTH / 2.,
+        },
+        Mesh2d(meshes.add(hull)),
+        MeshMaterial2d(materials.add(SUBMARINE_COLOR)),
+        // above every terrain layer
+        Transform::from_translation(coords.cell_to_world(map.spawn).extend(5.)),
+    ));
+}
+
+/// W and S thrust forwards and backwards, A and D turn and E runs the drill
+pub fn read_submarine_input(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut q_throttle: Query<(&mut Throttle, &mut Drill), With<Submarine>>,
+) {
+    let axis = |positive: KeyCode, negative: KeyCode| -> f32 {
+        return keyboard.pressed(positive) as u8 as f32 - keyboard.pressed(negative) as u8 as f32;
+    };
+
+    for (mut throttle, mut drill) in q_throttle.iter_mut() {
+        throttle.forward = axis(KeyCode::KeyW, KeyCode::KeyS);
+        throttle.turn = axis(KeyCode::KeyA, KeyCode::KeyD);
+        drill.active = keyboard.pressed(KeyCode::KeyE);
+    }
+}
+
+pub fn move_submarine(
+    mut q_submarine: Query<(&mut Transform, &mut Velocity, &Throttle, &SubmarineMovement)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+
+    for (mut transform, mut velocity, throttle, movement) in q_submarine.iter_mut() {
+        let thrust = if throttle.forward >= 0. {
+            movement.thrust
+        } else {
+            movement.reverse_thrust
+        };
+        let facing = transform.right().truncate();
+        let weight = Vec2::NEG_Y * GRAVITY * (1. - movement.buoyancy);
+
+        velocity.linear += (facing * thrust * throttle.forward + weight) * delta;
+        velocity.angular += movement.torque * throttle.turn * delta;
+
+        // drag scaled so it does not depend on the frame rate
+        velocity.linear *= (-movement.linear_drag * delta).exp();
+        velocity.angular *= (-movement.angular_drag * delta).exp();
+
+        transform.translation += (velocity.linear * delta).extend(0.);
+        transform.rotate_z(velocity.angular * delta);
+    }
+}
+
+pub fn run_drill(
+    q_drill: Query<(&Transform, &Collider, &Drill)>,
+    mut editor: TerrainEditor,
+    coords: Res<TerrainCoords>,
+    time: Res<Time>,
+) {
+    for (transform, collider, drill) in q_drill.iter() {
+        if !drill.active {
+            continue;
+        }
+
+        let area = drill_area(transform, collider, drill);
+        let min = coords.world_to_cell_position(area.center - area.half_size.length());
+        let max = coords.world_to_cell_position(area.center + area.half_size.length());
+
+        let mut cells = Vec::new();
+        for x in min.x.floor().max(0.) as usize..=(max.x.ceil() as usize).min(coords.width - 1) {
+            for y in min.y.floor().max(0.) as usize..=(max.y.ceil() as usize).min(coords.height - 1)
+            {
+                if area.contains(coords.cell_to_world((x, y))) {
+                    cells.push((x, y));
+                }
+            }
+        }
+
+        editor.damage_cells(
+            cells,
+            drill.speed * time.delta_secs(),
+            TerrainChangeCause::PlayerDrill,
+        );
+    }
+}
+
+pub fn draw_drill(q_drill: Query<(&Transform, &Collider, &Drill)>, mut gizmos: Gizmos) {
+    for (transform, collider, drill) in q_drill.iter() {
+        if !drill.active {
+            continue;
+        }
+
+        let area = drill_area(transform, collider, drill);
+        gizmos.rect_2d(
+            Isometry2d::new(area.center, Rot2::radians(area.angle)),
+            area.half_size * 2.,
+            DRILL_COLOR,
+        );
+    }
+}
+
+struct DrillArea {
+    center: Vec2,
+    half_size: Vec2,
+    /// Rotation of the area, its x axis points away from the submarine
+    angle: f32,
+}
+
+impl DrillArea {
+    fn contains(&self, point: Vec2) -> bool {
+        let local = Rot2::radians(-self.angle) * (point - self.center);
+        return local.x.abs() <= self.half_size.x && local.y.abs() <= self.half_size.y;
+    }
+}
+
+fn drill_area(transform: &Transform, collider: &Collider, drill: &Drill) -> DrillArea {
+    let facing = transform.right().truncate();
+    // starts a little inside the nose, so the wall it is touching is in reach
+    let start = collider.half_length + collider.radius / 2.;
+    let length = drill.reach + collider.radius / 2.;
+
+    return DrillArea {
+        center: transform.translation.truncate() + facing * (start + length / 2.),
+        half_size: Vec2::new(length, drill.width) / 2.,
+        angle: facing.to_angle(),
+    };
+}
+
+pub fn damage_hull(
+    mut commands: Commands,
+    mut terrain_impacts: EventReader<TerrainImpact>,
+    mut q_hull: Query<&mut Hull>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for impact in terrain_impacts.read() {
+        let Ok(mut hull) = q_hull.get_mut(impact.entity) else {
+            continue;
+        };
+        if impact.speed <= hull.damage_threshold {
+            continue;
+        }
+
+        let damage = (impact.speed - hull.damage_threshold)
+            * hull.damage_per_speed
+            * impact.tile.impact_damage_scale();
+        hull.integrity = (hull.integrity - damage).max(0.);
+
+        if hull.integrity <= 0. {
+            commands.insert_resource(GameOverReason::HullBreached);
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+pub fn use_supplies(
+    mut commands: Commands,
+    mut q_supplies: Query<(&mut Oxygen, &mut Battery, &Throttle, Option<&Drill>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_secs();
+
+    for (mut oxygen, mut battery, throttle, drill) in q_supplies.iter_mut() {
+        let drilling = drill.is_some_and(|drill| drill.active);
+        let exerting = throttle.forward != 0. || drilling;
+
+        let oxygen_use = oxygen.base_use + oxygen.exertion_use * exerting as u8 as f32;
+        let power_draw = battery.base_draw
+            + battery.thrust_draw * throttle.forward.abs()
+            + drill
+                .filter(|_| drilling)
+                .map_or(0., |drill| drill.power_draw);
+
+        oxygen.amount = (oxygen.amount - oxygen_use * delta).max(0.);
+        battery.charge = (battery.charge - power_draw * delta).max(0.);
+
+        if oxygen.amount <= 0. {
+            commands.insert_resource(GameOverReason::OutOfOxygen);
+            next_state.set(GameState::GameOver);
+        } else if battery.charge <= 0. {
+            commands.insert_resource(GameOverReason::OutOfPower);
+            next_state.set(GameState::GameOver);
+        }
+    }
+}
+
+/// Puts the submarine back at the spawn of a map that was just loaded,
+/// where it is sure to be in open water
+pub fn return_to_spawn(
+    mut q_submarine: Query<(&mut Transform, &mut Velocity), With<Submarine>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+) {
+    for (mut transform, mut velocity) in q_submarine.iter_mut() {
+        let spawn = coords.cell_to_world(map.spawn);
+        transform.translation.x = spawn.x;
+        transform.translation.y = spawn.y;
+        *velocity = Velocity::default();
+    }
+}
+
+pub fn explore_surroundings(q_sight: Query<(&Transform, &Sight)>, mut explorer: Explorer) {
+    for (transform, sight) in q_sight.iter() {
+        explorer.reveal_visible(transform.translation.truncate(), sight.radius);
+    }
+}
+
+/// While R is held, shows what is straight ahead of the submarine and
+/// where its hull would first touch a wall if it kept drifting
+pub fn draw_debug_casts(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    q_submarine: Query<(&Transform, &Velocity, &Collider), With<Submarine>>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+    mut gizmos: Gizmos,
+) {
+    if !keyboard.pressed(KeyCode::KeyR) {
+        return;
+    }
+
+    let reach = DEBUG_CAST_DISTANCE / coords.square_size;
+    for (transform, velocity, collider) in q_submarine.iter() {
+        let position = transform.translation.truncate();
+        let origin = coords.world_to_cell_position(position);
+
+        if let Some(hit) = map.raycast(origin, transform.right().truncate(), reach) {
+            let point = coords.cell_position_to_world(hit.point);
+            gizmos.line_2d(position, point, DEBUG_CAST_COLOR);
+            gizmos.arrow_2d(point, point + hit.normal * 10., DEBUG_CAST_COLOR);
+        }
+
+        let radius = collider.radius / coords.square_size;
+        if let Some(hit) = map.circle_cast(origin, radius, velocity.linear, reach) {
+            let direction = velocity.linear.normalize();
+            let center = position + direction * hit.distance * coords.square_size;
+            gizmos.circle_2d(center, collider.radius, DEBUG_CAST_COLOR);
+            gizmos.cross_2d(
+                Isometry2d::from_translation(coords.cell_position_to_world(hit.point)),
+                4.,
+                DEBUG_CAST_COLOR,
+            );
+        }
+    }
+}
+
+pub fn log_terrain_impacts(mut terrain_impacts: EventReader<TerrainImpact>) {
+    for impact in terrain_impacts.read() {
+        debug!(
+            "{} hit {:?} at {} going {} into the wall, pushed out along {}",
+            impact.entity, impact.tile, impact.point, impact.speed, impact.normal
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::ops::RangeInclusive;
+
+use bevy::math::Vec2;
+
+use super::{density::ISO_LEVEL, events::CellChange, resources::Map, tile::Tile};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BrushShape {
+    #[default]
+    Circle,
+    Square,
+}
+
+impl BrushShape {
+    /// Distance from the middle of the brush, a square's edge is the set of
+    /// points at the same distance like a circle's is
+    pub fn distance(&self, offset: Vec2) -> f32 {
+        return match self {
+            BrushShape::Circle => offset.length(),
+            BrushShape::Square => offset.abs().max_element(),
+        };
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrushMode {
+    Remove,
+    /// Fills water with the given tile
+    Add(Tile),
+}
+
+impl Map {
+    /// Every cell inside a brush centered on `center`, except the border
+    pub fn cells_in_brush(
+        &self,
+        center: Vec2,
+        radius: f32,
+        shape: BrushShape,
+    ) -> Vec<(usize, usize)> {
+        let (range_x, range_y) = self.brush_bounds(center, radius);
+        let mut cells = Vec::new();
+
+        for x in range_x {
+            for y in range_y.clone() {
+                let distance = shape.distance(Vec2::new(x as f32, y as f32) - center);
+                if distance <= radius && !self.is_border(x, y) {
+                    cells.push((x, y));
+                }
+            }
+        }
+
+        return cells;
+    }
+
+    /// Applies one stamp of a brush centered on `center`, measured in cells.
+    /// With a density field the edge of the brush is soft, so the contour
+    /// follows its exact shape instead of snapping to cells
+    pub fn stamp_brush(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        shape: BrushShape,
+        mode: BrushMode,
+    ) -> Vec<CellChange> {
+        let mut changed = Vec::new();
+
+        // one extra cell on every side for the soft edge
+        let (range_x, range_y) = self.brush_bounds(center, radius + 1.);
+
+        for x in range_x {
+            for y in range_y.clone() {
+                if self.is_border(x, y) || self.points[x][y] == Tile::Bedrock {
+                    continue;
+                }
+
+                let distance = shape.distance(Vec2::new(x as f32, y as f32) - center);
+                let previous = (self.points[x][y], self.density_at(x, y));
+
+                if self.density.is_some() {
+                    // the brush's own density is at the iso level right on its edge
+                    let edge = (distance - radius).clamp(-ISO_LEVEL, ISO_LEVEL);
+                    match mode {
+                        BrushMode::Remove => {
+                            self.set_density(x, y, previous.1.min(ISO_LEVEL + edge));
+                        }
+                        BrushMode::Add(tile) => {
+                            self.set_density(x, y, previous.1.max(ISO_LEVEL - edge));
+                            if !previous.0.is_solid() && self.is_solid(x, y) {
+                                self.points[x][y] = tile;
+                            }
+                        }
+                    }
+                } else if distance <= radius {
+                    match mode {
+                        BrushMode::Remove if previous.0.is_solid() => {
+                            self.set_tile(x, y, Tile::Water);
+                        }
+                        BrushMode::Add(tile) if !previous.0.is_solid() => {
+                            self.set_tile(x, y, tile);
+                        }
+                        _ => {}
+                    }
+                }
+
+                if (self.points[x][y], self.density_at(x, y)) != previous {
+                    changed.push(CellChange {
+                        cell: (x, y),
+                        previous: previous.0,
+                        new: self.points[x][y],
+                    });
+                }
+            }
+        }
+
+        return changed;
+    }
+
+    /// The cells within `reach` of `center` on both axes, clamped to the map
+    fn brush_bounds(
+        &self,
+        center: Vec2,
+        reach: f32,
+    ) -> (RangeInclusive<usize>, RangeInclusive<usize>) {
+        let min = (center - reach).max(Vec2::ZERO).floor();
+        let max = (center + reach).ceil();
+        let max_x = (max.x as usize).min(self.width - 1);
+        let max_y = (max.y as usize).min(self.height - 1);
+
+        return (min.x as usize..=max_x, min.y as usize..=max_y);
+    }
+}
@@ -4,36 +4,102 @@ use bevy::{
     render::mesh::{Indices, PrimitiveTopology},
 };
 
+use super::{
+    contour::{edge_crossing, square_segments},
+    coords::first_square,
+    resources::Map,
+    tile::Tile,
+};
+
 pub const CHUNK_SIZE: usize = 16;
 const PADDED_CHUNK_SIZE: usize = CHUNK_SIZE + 2;
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
 pub struct Chunk {
-    pub points: [[bool; PADDED_CHUNK_SIZE]; PADDED_CHUNK_SIZE],
+    pub points: [[Tile; PADDED_CHUNK_SIZE]; PADDED_CHUNK_SIZE],
+    pub density: [[f32; PADDED_CHUNK_SIZE]; PADDED_CHUNK_SIZE],
+    /// The column and row of the first square meshed, see [`first_square`]
+    pub first_square: (usize, usize),
 }
 
 impl Chunk {
-    pub fn new(map: Vec<Vec<bool>>) -> Self {
-        let mut points = [[false; PADDED_CHUNK_SIZE]; PADDED_CHUNK_SIZE];
+    /// Copies the chunk at (`chunk_x`, `chunk_y`) out of the map, along with
+    /// the points shared with its neighbours
+    pub fn new(map: &Map, chunk_x: usize, chunk_y: usize) -> Self {
+        let mut points = [[Tile::Water; PADDED_CHUNK_SIZE]; PADDED_CHUNK_SIZE];
+        let mut density = [[0.; PADDED_CHUNK_SIZE]; PADDED_CHUNK_SIZE];
 
         for x in 0..PADDED_CHUNK_SIZE {
             for y in 0..PADDED_CHUNK_SIZE {
-                points[x][y] = map[x][y];
+                let map_x = chunk_x * CHUNK_SIZE + x;
+                let map_y = chunk_y * CHUNK_SIZE + y;
+
+                points[x][y] = map.points[map_x][map_y];
+                density[x][y] = map.density_at(map_x, map_y);
+            }
+        }
+
+        Self {
+            points,
+            density,
+            first_square: first_square(UVec2::new(chunk_x as u32, chunk_y as u32)),
+        }
+    }
+
+    /// See [`edge_crossing`]
+    fn edge_crossing(&self, a: (usize, usize), b: (usize, usize)) -> f32 {
+        return edge_crossing(self.density[a.0][a.1], self.density[b.0][b.1]);
+    }
+
+    /// The boundary between water and the walls as line segments, in the
+    /// same space as the chunk's meshes. Every segment has the wall on its
+    /// left, so its right hand normal points into the water
+    pub fn generate_contours(&self, square_size: f32) -> Vec<[Vec2; 2]> {
+        let mut segments = Vec::new();
+
+        let (first_col, first_row) = self.first_square;
+        for row in first_row..=CHUNK_SIZE {
+            for col in first_col..=CHUNK_SIZE {
+                let corners = [
+                    (col, row),
+                    (col + 1, row),
+                    (col + 1, row + 1),
+                    (col, row + 1),
+                ]
+                .map(|(x, y)| self.points[x][y].is_solid());
+                let crossings = [
+                    self.edge_crossing((col, row), (col + 1, row)),
+                    self.edge_crossing((col + 1, row), (col + 1, row + 1)),
+                    self.edge_crossing((col, row + 1), (col + 1, row + 1)),
+                    self.edge_crossing((col, row), (col, row + 1)),
+                ];
+
+                let origin = Vec2::new(col as f32, row as f32) * square_size
+                    - CHUNK_SIZE as f32 * square_size / 2.;
+                for [a, b] in square_segments(corners, crossings) {
+                    segments.push([origin + a * square_size, origin + b * square_size]);
+                }
             }
         }
 
-        Self { points }
+        return segments;
     }
 
+    /// Marching squares over every point made of `layer` or a tile that
+    /// comes after it. Each solid layer is drawn on top of the previous one,
+    /// so the first solid layer gives the outline of all the walls and later
+    /// layers cover it where their material is, without leaving any gaps
+    /// along the boundaries between materials
     pub fn generate_vertices(
         &self,
         square_size: f32,
+        layer: Tile,
     ) -> (Vec<[f32; 3]>, Vec<[f32; 3]>, Vec<[f32; 2]>, Vec<u32>) {
         let get_point_int = |x: usize, y: usize| -> u8 {
             if x >= CHUNK_SIZE + 2 || y >= CHUNK_SIZE + 2 {
                 return 1;
             }
-            return self.points[x][y] as u8;
+            return (self.points[x][y] >= layer) as u8;
         };
 
         let mut positions: Vec<[f32; 3]> = Vec::new();
@@ -42,23 +108,24 @@ impl Chunk {
         let mut indices: Vec<u32> = Vec::new();
 
         // Iterate over 4 grid points at a time
-        for row in 1..=CHUNK_SIZE {
-            for col in 1..=CHUNK_SIZE {
+        let (first_col, first_row) = self.first_square;
+        for row in first_row..=CHUNK_SIZE {
+            for col in first_col..=CHUNK_SIZE {
                 let value = (get_point_int(col, row) * 8
                     + get_point_int(col + 1, row) * 4
                     + get_point_int(col + 1, row + 1) * 2
                     + get_point_int(col, row + 1) * 1) as u8;
 
-                let left_x = col as f32 * square_size - (16.0 * square_size) / 2.;
-                let top_y = row as f32 * square_size - (16.0 * square_size) / 2.;
+                let left_x = col as f32 * square_size - (CHUNK_SIZE as f32 * square_size) / 2.;
+                let top_y = row as f32 * square_size - (CHUNK_SIZE as f32 * square_size) / 2.;
 
                 let right_x = left_x + square_size;
                 let bottom_y = top_y + square_size;
 
-                let top = 0.5;
-                let right = 0.5;
-                let bottom = 0.5;
-                let left = 0.5;
+                let top = self.edge_crossing((col, row), (col + 1, row));
+                let right = self.edge_crossing((col + 1, row), (col + 1, row + 1));
+                let bottom = self.edge_crossing((col, row + 1), (col + 1, row + 1));
+                let left = self.edge_crossing((col, row), (col, row + 1));
 
                 match value {
                     0 => {}
@@ -380,55 +447,21 @@ impl Chunk {
 
         return (positions, normals, uvs, indices);
     }
-}
 
-pub struct ChunkMap {
-    pub map: Vec<Vec<Chunk>>,
-    pub square_size: f32,
-}
+    /// A mesh with no triangles, used as a placeholder until a chunk's real
+    /// mesh has been built
+    pub fn empty_mesh() -> Mesh {
+        let mut new_mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        new_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new());
+        new_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, Vec::<[f32; 3]>::new());
+        new_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, Vec::<[f32; 2]>::new());
+        new_mesh.insert_indices(Indices::U32(Vec::new()));
 
-impl ChunkMap {
-    pub fn new(base_map: Vec<Vec<bool>>, square_size: f32) -> Self {
-        const PADDED_CHUNK_SIZE: usize = CHUNK_SIZE + 2;
-
-        let width = base_map[0].len();
-        let height = base_map.len();
-
-        let chunk_x_count = width / CHUNK_SIZE;
-        let chunk_y_count = height / CHUNK_SIZE;
-
-        let base_map = base_map.into_iter().flatten().collect::<Vec<bool>>();
-
-        let map = (0..chunk_y_count)
-            .into_iter()
-            .map(|y| {
-                (0..chunk_x_count)
-                    .into_iter()
-                    .map(|x| {
-                        let points = (0..PADDED_CHUNK_SIZE)
-                            .into_iter()
-                            .map(|i| {
-                                let start = x * CHUNK_SIZE + i * width + y * width * CHUNK_SIZE;
-                                base_map[start..(start + PADDED_CHUNK_SIZE)].to_vec()
-                            })
-                            .collect::<Vec<Vec<bool>>>();
-
-                        return Chunk::new(points);
-                    })
-                    .collect::<Vec<Chunk>>()
-            })
-            .collect::<Vec<Vec<Chunk>>>();
-
-        Self { map, square_size }
+        return new_mesh;
     }
 
-    pub fn chunk_mesh(
-        &self,
-        meshes: &mut ResMut<Assets<Mesh>>,
-        x: usize,
-        y: usize,
-    ) -> Handle<Mesh> {
-        let (positions, normals, uvs, indices) = self.map[x][y].generate_vertices(self.square_size);
+    pub fn build_mesh(&self, square_size: f32, layer: Tile) -> Mesh {
+        let (positions, normals, uvs, indices) = self.generate_vertices(square_size, layer);
 
         let mut new_mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
         new_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
@@ -436,18 +469,6 @@ impl ChunkMap {
         new_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
         new_mesh.insert_indices(Indices::U32(indices));
 
-        return meshes.add(new_mesh);
-    }
-
-    pub fn all_chunk_meshes(&self, meshes: &mut ResMut<Assets<Mesh>>) -> Vec<Vec<Handle<Mesh>>> {
-        let mut handles = vec![Vec::new(); self.map.len()];
-
-        for x in 0..self.map.len() {
-            for y in 0..self.map[x].len() {
-                handles[x].push(self.chunk_mesh(meshes, x, y));
-            }
-        }
-
-        return handles;
+        return new_mesh;
     }
 }
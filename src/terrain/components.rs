@@ -1,12 +1,25 @@
 use bevy::prelude::*;
 
+use super::tile::Tile;
+
 #[derive(Component)]
 pub struct TerrainMesh {
     pub chunk_position: UVec2,
+    /// The solid tile this mesh draws, see [`Chunk::generate_vertices`](super::chunk::Chunk::generate_vertices)
+    pub layer: Tile,
+}
+
+/// The fog of war over one chunk, see [`FogOfWar`](super::resources::FogOfWar)
+#[derive(Component)]
+pub struct FogMesh {
+    pub chunk_position: UVec2,
 }
 
 impl TerrainMesh {
-    pub fn new(chunk_position: UVec2) -> Self {
-        Self { chunk_position }
+    pub fn new(chunk_position: UVec2, layer: Tile) -> Self {
+        Self {
+            chunk_position,
+            layer,
+        }
     }
 }
@@ -0,0 +1,165 @@
+use bevy::{math::Vec2, platform::collections::HashMap};
+
+use super::{density::ISO_LEVEL, resources::Map};
+
+/// How far along an edge from a point of `density_a` to one of `density_b`
+/// the contour crosses it. Only edges between solid and water are
+/// interpolated, boundaries between two materials stay in the middle
+pub fn edge_crossing(density_a: f32, density_b: f32) -> f32 {
+    if (density_a >= ISO_LEVEL) == (density_b >= ISO_LEVEL) {
+        return 0.5;
+    }
+
+    return ((ISO_LEVEL - density_a) / (density_b - density_a)).clamp(0., 1.);
+}
+
+/// The contour segments inside one marching square, in a unit square with the
+/// bottom left corner at the origin. `corners` are bottom left, bottom right,
+/// top right and top left, the same bits the mesher builds its case from.
+/// `crossings` are how far along the bottom, right, top and left edges the
+/// contour crosses them, measured from the bottom or left end. Every segment
+/// has the solid side on its left
+pub fn square_segments(corners: [bool; 4], crossings: [f32; 4]) -> Vec<[Vec2; 2]> {
+    let [bottom, right, top, left] = crossings;
+    let bottom = Vec2::new(bottom, 0.);
+    let right = Vec2::new(1., right);
+    let top = Vec2::new(top, 1.);
+    let left = Vec2::new(0., left);
+
+    let case =
+        corners[0] as u8 * 8 + corners[1] as u8 * 4 + corners[2] as u8 * 2 + corners[3] as u8;
+    let segments = match case {
+        1 | 14 => vec![[left, top]],
+        2 | 13 => vec![[top, right]],
+        3 | 12 => vec![[left, right]],
+        4 | 11 => vec![[bottom, right]],
+        // the saddles keep their two solid corners apart, like the mesher does
+        5 => vec![[left, top], [bottom, right]],
+        10 => vec![[top, right], [left, bottom]],
+        6 | 9 => vec![[bottom, top]],
+        7 | 8 => vec![[left, bottom]],
+        _ => vec![],
+    };
+
+    let corner_positions = [Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y];
+    return segments
+        .into_iter()
+        .map(|[a, b]| {
+            // the solid corner closest to a segment is always on its solid side
+            let middle = (a + b) / 2.;
+            let solid_corner = (0..4)
+                .filter(|&corner| corners[corner])
+                .map(|corner| corner_positions[corner])
+                .min_by(|p, q| {
+                    p.distance_squared(middle)
+                        .total_cmp(&q.distance_squared(middle))
+                })
+                .unwrap();
+
+            if (b - a).perp_dot(solid_corner - a) >= 0. {
+                return [a, b];
+            }
+            return [b, a];
+        })
+        .collect();
+}
+
+/// Joins segments that share end points into polylines. Closed loops repeat
+/// their first point at the end. End points closer than `tolerance` are
+/// treated as the same point
+pub fn stitch_segments<'a>(
+    segments: impl IntoIterator<Item = &'a [Vec2; 2]>,
+    tolerance: f32,
+) -> Vec<Vec<Vec2>> {
+    let key = |point: Vec2| -> (i32, i32) {
+        return (
+            (point.x / tolerance).round() as i32,
+            (point.y / tolerance).round() as i32,
+        );
+    };
+
+    let segments: Vec<[Vec2; 2]> = segments.into_iter().copied().collect();
+    // segments are oriented, so every point starts at most one segment
+    let mut starting_at = HashMap::new();
+    let mut ending_at = HashMap::new();
+    for (index, [start, end]) in segments.iter().enumerate() {
+        starting_at.insert(key(*start), index);
+        ending_at.insert(key(*end), index);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut polylines = Vec::new();
+
+    // open chains first so they are followed from their real start, then
+    // whatever is left only forms loops
+    let chain_starts =
+        (0..segments.len()).filter(|&index| !ending_at.contains_key(&key(segments[index][0])));
+    let order: Vec<usize> = chain_starts.chain(0..segments.len()).collect();
+
+    for first in order {
+        if used[first] {
+            continue;
+        }
+
+        let mut polyline = vec![segments[first][0]];
+        let mut current = first;
+        loop {
+            used[current] = true;
+            let end = segments[current][1];
+            polyline.push(end);
+
+            match starting_at.get(&key(end)) {
+                Some(&next) if !used[next] => current = next,
+                _ => break,
+            }
+        }
+
+        polylines.push(polyline);
+    }
+
+    return polylines;
+}
+
+impl Map {
+    /// The contour of every square overlapping the box from `min` to `max`,
+    /// measured in cells, built the same way as the chunk meshes so it
+    /// matches what is drawn. Reads the map directly, so it is up to date
+    /// even before the chunks around an edit are rebuilt
+    pub fn contour_segments_in(&self, min: Vec2, max: Vec2) -> Vec<[Vec2; 2]> {
+        let min = min.floor().max(Vec2::ZERO);
+        let max = max.floor().max(Vec2::ZERO);
+        // squares are named after their bottom left cell
+        let max_x = (max.x as usize).min(self.width - 2);
+        let max_y = (max.y as usize).min(self.height - 2);
+
+        let mut segments = Vec::new();
+        for x in min.x as usize..=max_x {
+            for y in min.y as usize..=max_y {
+                segments.extend(self.square_contour((x, y)));
+            }
+        }
+
+        return segments;
+    }
+
+    /// The contour inside the marching square with `square` as its bottom
+    /// left cell, measured in cells. The square must not be on the top or
+    /// right edge of the map
+    pub fn square_contour(&self, (x, y): (usize, usize)) -> Vec<[Vec2; 2]> {
+        let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+        let density = corners.map(|(x, y)| self.density_at(x, y));
+        let crossings = [
+            edge_crossing(density[0], density[1]),
+            edge_crossing(density[1], density[2]),
+            edge_crossing(density[3], density[2]),
+            edge_crossing(density[0], density[3]),
+        ];
+
+        let origin = Vec2::new(x as f32, y as f32);
+        let solid = corners.map(|(x, y)| self.is_solid(x, y));
+        return square_segments(solid, crossings)
+            .into_iter()
+            .map(|[a, b]| [origin + a, origin + b])
+            .collect();
+    }
+}
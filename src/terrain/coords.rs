@@ -0,0 +1,151 @@
+use bevy::{
+    math::{Rect, UVec2, Vec2},
+    prelude::Resource,
+};
+
+use super::{chunk::CHUNK_SIZE, resources::TerrainConfig};
+
+/// Cells of bedrock around the chunks on every side of the map
+pub const MAP_PADDING: usize = 1;
+
+/// The local cell of the first square `chunk` meshes. The chunks along the
+/// left and bottom of the map also mesh the squares between the padding and
+/// the edge, like the ones along the right and top already do, so the map
+/// is walled in on every side
+pub fn first_square(chunk: UVec2) -> (usize, usize) {
+    let first = |chunk: u32| if chunk == 0 { 0 } else { MAP_PADDING };
+    return (first(chunk.x), first(chunk.y));
+}
+
+/// Conversions between world positions, map cells and chunks. A cell is a
+/// point of [`Map::points`](super::resources::Map::points), chunk `(x, y)`
+/// owns the cells `x * CHUNK_SIZE + MAP_PADDING` up to and including
+/// `(x + 1) * CHUNK_SIZE`, and its meshes are drawn around its origin
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct TerrainCoords {
+    pub square_size: f32,
+    pub chunks_x: usize,
+    pub chunks_y: usize,
+    /// Size of the map in cells, padding included
+    pub width: usize,
+    pub height: usize,
+}
+
+impl TerrainCoords {
+    pub fn from_config(config: &TerrainConfig) -> Self {
+        return Self {
+            square_size: config.square_size,
+            chunks_x: config.chunks_x,
+            chunks_y: config.chunks_y,
+            width: config.chunks_x * CHUNK_SIZE + 2 * MAP_PADDING,
+            height: config.chunks_y * CHUNK_SIZE + 2 * MAP_PADDING,
+        };
+    }
+
+    /// World position of a cell. The chunk meshes are centered on their
+    /// chunk origin, so cell `0` sits half a chunk before the first origin
+    pub fn cell_to_world(&self, (x, y): (usize, usize)) -> Vec2 {
+        return self.cell_position_to_world(Vec2::new(x as f32, y as f32));
+    }
+
+    /// The inverse of [`TerrainCoords::world_to_cell_position`]
+    pub fn cell_position_to_world(&self, pos: Vec2) -> Vec2 {
+        return (pos - CHUNK_SIZE as f32 / 2.) * self.square_size;
+    }
+
+    /// `pos` measured in cells, so cell `(x, y)` is at `(x, y)`
+    pub fn world_to_cell_position(&self, pos: Vec2) -> Vec2 {
+        return pos / self.square_size + CHUNK_SIZE as f32 / 2.;
+    }
+
+    /// The cell closest to `pos`, if it is inside the map
+    pub fn world_to_cell(&self, pos: Vec2) -> Option<(usize, usize)> {
+        let cell = self.world_to_cell_position(pos).round();
+        if cell.x < 0. || cell.y < 0. {
+            return None;
+        }
+
+        let cell = (cell.x as usize, cell.y as usize);
+        if !self.contains_cell(cell) {
+            return None;
+        }
+
+        return Some(cell);
+    }
+
+    pub fn contains_cell(&self, (x, y): (usize, usize)) -> bool {
+        return x < self.width && y < self.height;
+    }
+
+    /// The chunk that owns a cell and where the cell is in that chunk's
+    /// [`Chunk::points`](super::chunk::Chunk::points). The padding belongs
+    /// to the chunks along the edge of the map
+    pub fn cell_to_chunk(&self, (x, y): (usize, usize)) -> (UVec2, (usize, usize)) {
+        let chunk_x = (x.saturating_sub(MAP_PADDING) / CHUNK_SIZE).min(self.chunks_x - 1);
+        let chunk_y = (y.saturating_sub(MAP_PADDING) / CHUNK_SIZE).min(self.chunks_y - 1);
+
+        let local = (x - chunk_x * CHUNK_SIZE, y - chunk_y * CHUNK_SIZE);
+        return (UVec2::new(chunk_x as u32, chunk_y as u32), local);
+    }
+
+    /// The inverse of [`TerrainCoords::cell_to_chunk`]
+    pub fn chunk_to_cell(&self, chunk: UVec2, (x, y): (usize, usize)) -> (usize, usize) {
+        return (
+            chunk.x as usize * CHUNK_SIZE + x,
+            chunk.y as usize * CHUNK_SIZE + y,
+        );
+    }
+
+    /// Every chunk whose meshes depend on a cell. Cells on the edge of a
+    /// chunk are shared with its neighbours, diagonal ones included
+    pub fn chunks_touching_cell(&self, cell: (usize, usize)) -> Vec<UVec2> {
+        let (owner, local) = self.cell_to_chunk(cell);
+        // a chunk meshes the squares up to its local cell CHUNK_SIZE +
+        // MAP_PADDING, which is the first cell of the next chunk
+        let range = |owner: u32, local: usize| {
+            if local == MAP_PADDING && owner > 0 {
+                return owner - 1..=owner;
+            }
+            return owner..=owner;
+        };
+
+        let mut chunks = Vec::new();
+        for chunk_x in range(owner.x, local.0) {
+            for chunk_y in range(owner.y, local.1) {
+                chunks.push(UVec2::new(chunk_x, chunk_y));
+            }
+        }
+
+        return chunks;
+    }
+
+    /// Every chunk of the map
+    pub fn chunks(&self) -> impl Iterator<Item = UVec2> {
+        let chunks_y = self.chunks_y as u32;
+        return (0..self.chunks_x as u32)
+            .flat_map(move |x| (0..chunks_y).map(move |y| UVec2::new(x, y)));
+    }
+
+    /// World position of a chunk's entities
+    pub fn chunk_origin(&self, chunk: UVec2) -> Vec2 {
+        return chunk.as_vec2() * CHUNK_SIZE as f32 * self.square_size;
+    }
+
+    /// The area covered by a chunk's meshes
+    pub fn chunk_bounds(&self, chunk: UVec2) -> Rect {
+        return Rect::from_corners(
+            self.cell_to_world(self.chunk_to_cell(chunk, first_square(chunk))),
+            self.cell_to_world(
+                self.chunk_to_cell(chunk, (CHUNK_SIZE + MAP_PADDING, CHUNK_SIZE + MAP_PADDING)),
+            ),
+        );
+    }
+
+    /// The area covered by all chunk meshes
+    pub fn bounds(&self) -> Rect {
+        return Rect::from_corners(
+            self.cell_to_world((0, 0)),
+            self.cell_to_world((self.width - 1, self.height - 1)),
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use bevy::prelude::*;
+
+use super::{
+    brush::BrushShape, coords::TerrainCoords, density::ISO_LEVEL, events::CellChange,
+    resources::Map, tile::Tile,
+};
+
+/// A blast that removes the terrain around `center`. Send it as an event
+/// to have it carved by the [`TerrainEditor`](super::editor::TerrainEditor)
+#[derive(Event, Clone, Copy, Debug)]
+pub struct Crater {
+    /// World position of the blast
+    pub center: Vec2,
+    /// World distance the blast reaches with no falloff
+    pub radius: f32,
+    /// Tiles up to this [`Tile::hardness`] break in the middle of the blast
+    pub strength: f32,
+    /// How much weaker the blast gets towards its edge, from 0 for the full
+    /// strength all the way out to 1 for nothing left at the radius
+    pub falloff: f32,
+}
+
+impl Crater {
+    /// How far from the center, in cells, a tile of `hardness` still
+    /// breaks. `None` when the blast is too weak to break it anywhere
+    pub fn reach(&self, hardness: f32, square_size: f32) -> Option<f32> {
+        if hardness > self.strength {
+            return None;
+        }
+
+        let radius = self.radius / square_size;
+        if self.falloff <= 0. {
+            return Some(radius);
+        }
+
+        return Some((radius * (1. - hardness / self.strength) / self.falloff).min(radius));
+    }
+}
+
+impl Map {
+    /// Blows a hole in the map. Harder tiles resist more of the blast, so
+    /// they are left standing closer to its center, and tiles without a
+    /// hardness are never removed. Returns every cell that changed
+    pub fn carve_crater(&mut self, coords: &TerrainCoords, crater: &Crater) -> Vec<CellChange> {
+        let center = coords.world_to_cell_position(crater.center);
+        let mut changed = Vec::new();
+
+        // one extra cell on every side for the soft edge
+        let cells = self.cells_in_brush(
+            center,
+            crater.radius / coords.square_size + 1.,
+            BrushShape::Circle,
+        );
+
+        for (x, y) in cells {
+            let previous = (self.points[x][y], self.density_at(x, y));
+            let hardness = match previous.0 {
+                // water is lowered too, so the contour next to it moves along
+                Tile::Water => 0.,
+                tile => match tile.hardness() {
+                    Some(hardness) => hardness,
+                    None => continue,
+                },
+            };
+
+            let Some(reach) = crater.reach(hardness, coords.square_size) else {
+                continue;
+            };
+            let distance = Vec2::new(x as f32, y as f32).distance(center);
+
+            if self.density.is_some() {
+                // the same soft edge as a brush, see `Map::stamp_brush`
+                let edge = (distance - reach).clamp(-ISO_LEVEL, ISO_LEVEL);
+                self.set_density(x, y, previous.1.min(ISO_LEVEL + edge));
+            } else if distance <= reach && previous.0.is_solid() {
+                self.set_tile(x, y, Tile::Water);
+            }
+
+            if (self.points[x][y], self.density_at(x, y)) != previous {
+                changed.push(CellChange {
+                    cell: (x, y),
+                    previous: previous.0,
+                    new: self.points[x][y],
+                });
+            }
+        }
+
+        return changed;
+    }
+}
@@ -0,0 +1,78 @@
+use super::{resources::Map, tile::Tile};
+
+/// Points with a density at or above this are solid
+pub const ISO_LEVEL: f32 = 0.5;
+
+impl Map {
+    /// Builds a density field from the tiles, giving each point a value
+    /// based on how much of its neighbourhood is solid. Solid points stay at
+    /// or above [`ISO_LEVEL`] and water stays below it, so the tiles don't
+    /// change but the contour between them bends towards the thinner side
+    pub fn generate_density(&mut self) {
+        let mut density = vec![vec![0.; self.height]; self.width];
+
+        for (x, column) in density.iter_mut().enumerate() {
+            for (y, value) in column.iter_mut().enumerate() {
+                let mut solid_neighbors = 0;
+
+                for offset_x in -1..=1 {
+                    for offset_y in -1..=1 {
+                        let target_x = x.checked_add_signed(offset_x);
+                        let target_y = y.checked_add_signed(offset_y);
+
+                        // outside the map counts as solid, same as when meshing
+                        let solid = match (target_x, target_y) {
+                            (Some(target_x), Some(target_y))
+                                if self.is_in_map(target_x, target_y) =>
+                            {
+                                self.is_solid(target_x, target_y)
+                            }
+                            _ => true,
+                        };
+
+                        solid_neighbors += solid as usize;
+                    }
+                }
+
+                let solid_fraction = solid_neighbors as f32 / 9.;
+                *value = if self.is_solid(x, y) {
+                    ISO_LEVEL + (1. - ISO_LEVEL) * solid_fraction
+                } else {
+                    ISO_LEVEL * solid_fraction
+                };
+            }
+        }
+
+        self.density = Some(density);
+    }
+
+    /// The density at (`x`, `y`), maps without a density field are either
+    /// fully solid or fully empty at every point
+    pub fn density_at(&self, x: usize, y: usize) -> f32 {
+        return match &self.density {
+            Some(density) => density[x][y],
+            None => self.is_solid(x, y) as u8 as f32,
+        };
+    }
+
+    /// Sets the density at (`x`, `y`). A solid point whose density drops
+    /// below [`ISO_LEVEL`] turns to water and water rising above it turns
+    /// to rock, bedrock never changes. Does nothing on maps without density
+    pub fn set_density(&mut self, x: usize, y: usize, value: f32) {
+        if self.points[x][y] == Tile::Bedrock {
+            return;
+        }
+
+        let Some(density) = &mut self.density else {
+            return;
+        };
+
+        density[x][y] = value.clamp(0., 1.);
+
+        if value < ISO_LEVEL {
+            self.points[x][y] = Tile::Water;
+        } else if !self.points[x][y].is_solid() {
+            self.points[x][y] = Tile::Rock;
+        }
+    }
+}
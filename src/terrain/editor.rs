@@ -0,0 +1,111 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{
+    brush::{BrushMode, BrushShape},
+    coords::TerrainCoords,
+    crater::Crater,
+    events::{CellChange, TerrainChangeCause, TerrainModified},
+    resources::{ChunksPendingRebuild, DigProgress, Map, UnsupportedCells},
+    tile::Tile,
+};
+
+/// The one place the map should be edited from once the game is running.
+/// Every edit marks the chunks it touched for rebuilding and sends a
+/// [`TerrainModified`] with the cells it changed
+#[derive(SystemParam)]
+pub struct TerrainEditor<'w> {
+    map: ResMut<'w, Map>,
+    coords: Res<'w, TerrainCoords>,
+    chunks_pending_rebuild: ResMut<'w, ChunksPendingRebuild>,
+    dig_progress: ResMut<'w, DigProgress>,
+    unsupported_cells: ResMut<'w, UnsupportedCells>,
+    terrain_modified: EventWriter<'w, TerrainModified>,
+}
+
+impl TerrainEditor<'_> {
+    /// See [`Map::stamp_brush`]
+    pub fn stamp_brush(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        shape: BrushShape,
+        mode: BrushMode,
+        cause: TerrainChangeCause,
+    ) {
+        let changes = self.map.stamp_brush(center, radius, shape, mode);
+        self.apply(changes, cause);
+    }
+
+    /// See [`Map::carve_crater`]
+    pub fn carve_crater(&mut self, crater: &Crater, cause: TerrainChangeCause) {
+        let changes = self.map.carve_crater(&self.coords, crater);
+        self.apply(changes, cause);
+    }
+
+    /// Mines each of `cells` for `amount` seconds at a dig power of 1. A
+    /// cell breaks into water once the damage adds up to its
+    /// [`Tile::hardness`], tiles without one never break
+    pub fn damage_cells(
+        &mut self,
+        cells: impl IntoIterator<Item = (usize, usize)>,
+        amount: f32,
+        cause: TerrainChangeCause,
+    ) {
+        let mut changes = Vec::new();
+
+        for (x, y) in cells {
+            let previous = self.map.points[x][y];
+            let Some(hardness) = previous.hardness() else {
+                continue;
+            };
+            if self.map.is_border(x, y) {
+                continue;
+            }
+
+            let progress = self.dig_progress.cells.entry((x, y)).or_insert(0.);
+            *progress += amount / hardness;
+            if *progress < 1. {
+                continue;
+            }
+
+            self.map.set_tile(x, y, Tile::Water);
+            changes.push(CellChange {
+                cell: (x, y),
+                previous,
+                new: Tile::Water,
+            });
+        }
+
+        self.apply(changes, cause);
+    }
+
+    /// Lets the loose tiles above every cell that turned into water since
+    /// the last call fall by one cell. Whatever they leave behind is
+    /// checked on the next call, so a column settles over a few frames
+    pub fn collapse_loose_tiles(&mut self) {
+        let cells = std::mem::take(&mut self.unsupported_cells.cells);
+        let changes = self.map.collapse_loose_tiles(&cells);
+        self.apply(changes, TerrainChangeCause::Collapse);
+    }
+
+    fn apply(&mut self, changes: Vec<CellChange>, cause: TerrainChangeCause) {
+        if changes.is_empty() {
+            return;
+        }
+
+        for change in changes.iter() {
+            // whatever the cell is now, it starts out undamaged
+            self.dig_progress.cells.remove(&change.cell);
+            if !change.new.is_solid() {
+                self.unsupported_cells.cells.push(change.cell);
+            }
+            // cells on the edge of a chunk are shared with its neighbours
+            self.chunks_pending_rebuild
+                .chunks
+                .extend(self.coords.chunks_touching_cell(change.cell));
+        }
+
+        self.terrain_modified
+            .write(TerrainModified { changes, cause });
+    }
+}
@@ -0,0 +1,39 @@
+use bevy::prelude::*;
+
+use super::tile::Tile;
+
+/// What changed the terrain
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TerrainChangeCause {
+    /// Broken by mining, see [`TerrainEditor::damage_cells`](super::editor::TerrainEditor::damage_cells)
+    PlayerDrill,
+    /// Blown away by a [`Crater`](super::crater::Crater)
+    Explosion,
+    /// Painted with the [`TerrainBrush`](super::resources::TerrainBrush)
+    Editor,
+    /// Loose tiles falling into water below them, see
+    /// [`TerrainEditor::collapse_loose_tiles`](super::editor::TerrainEditor::collapse_loose_tiles)
+    Collapse,
+}
+
+/// One cell touched by an edit. The tile can stay the same when only the
+/// density of the cell changed
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CellChange {
+    pub cell: (usize, usize),
+    pub previous: Tile,
+    pub new: Tile,
+}
+
+/// Sent when a saved map replaced the running one, so whatever was placed
+/// from the old map's points of interest can be placed again
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MapLoaded;
+
+/// Sent by the [`TerrainEditor`](super::editor::TerrainEditor) once for
+/// every edit of the map
+#[derive(Event, Clone, Debug)]
+pub struct TerrainModified {
+    pub changes: Vec<CellChange>,
+    pub cause: TerrainChangeCause,
+}
@@ -0,0 +1,195 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    ecs::system::SystemParam,
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology},
+};
+
+use super::{
+    chunk::CHUNK_SIZE,
+    coords::{first_square, TerrainCoords, MAP_PADDING},
+    resources::{FogOfWar, Map},
+};
+
+/// How the parts of the map that have not been explored yet are drawn
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum FogMode {
+    /// Covered completely
+    #[default]
+    Hidden,
+    /// Drawn dimmed
+    Darkened,
+    /// The whole map is visible
+    Off,
+}
+
+impl FogMode {
+    /// Opacity of the fog over an unexplored cell
+    pub fn opacity(&self) -> f32 {
+        return match self {
+            FogMode::Hidden => 1.,
+            FogMode::Darkened => 0.6,
+            FogMode::Off => 0.,
+        };
+    }
+
+    pub fn next(&self) -> Self {
+        return match self {
+            FogMode::Hidden => FogMode::Darkened,
+            FogMode::Darkened => FogMode::Off,
+            FogMode::Off => FogMode::Hidden,
+        };
+    }
+}
+
+impl Map {
+    pub fn is_explored(&self, x: usize, y: usize) -> bool {
+        return self.explored[x][y];
+    }
+
+    /// Marks every cell within `radius` of `center` as explored, both
+    /// measured in cells. Returns the cells that were not explored before
+    pub fn explore_circle(&mut self, center: Vec2, radius: f32) -> Vec<(usize, usize)> {
+        let mut explored = Vec::new();
+        for (x, y) in self.cells_in_circle(center, radius) {
+            if !self.explored[x][y] {
+                self.explored[x][y] = true;
+                explored.push((x, y));
+            }
+        }
+
+        return explored;
+    }
+
+    /// Like [`Map::explore_circle`], but only the cells that can be seen
+    /// from `center`. A wall is seen up to a cell deep past its surface
+    pub fn explore_visible(&mut self, center: Vec2, radius: f32) -> Vec<(usize, usize)> {
+        let mut explored = Vec::new();
+        for (x, y) in self.cells_in_circle(center, radius) {
+            if self.explored[x][y] {
+                continue;
+            }
+
+            let cell = Vec2::new(x as f32, y as f32);
+            let distance = cell.distance(center);
+            let visible = self
+                .raycast(center, cell - center, distance)
+                .is_none_or(|hit| distance - hit.distance <= 1.);
+            if visible {
+                self.explored[x][y] = true;
+                explored.push((x, y));
+            }
+        }
+
+        return explored;
+    }
+
+    /// Every cell within `radius` of `center`, both measured in cells
+    fn cells_in_circle(&self, center: Vec2, radius: f32) -> Vec<(usize, usize)> {
+        let min = (center - radius).floor().max(Vec2::ZERO);
+        let max = (center + radius).ceil();
+        let max_x = (max.x as usize).min(self.width - 1);
+        let max_y = (max.y as usize).min(self.height - 1);
+
+        let mut cells = Vec::new();
+        for x in min.x as usize..=max_x {
+            for y in min.y as usize..=max_y {
+                if Vec2::new(x as f32, y as f32).distance(center) <= radius {
+                    cells.push((x, y));
+                }
+            }
+        }
+
+        return cells;
+    }
+
+    /// The fog over a chunk, one vertex per cell it meshes. The color's
+    /// alpha fades in between explored and unexplored cells
+    pub fn build_fog_mesh(
+        &self,
+        coords: &TerrainCoords,
+        chunk: UVec2,
+        color: Color,
+        mode: FogMode,
+    ) -> Mesh {
+        let (first_x, first_y) = first_square(chunk);
+        let size_x = CHUNK_SIZE + MAP_PADDING + 1 - first_x;
+        let size_y = CHUNK_SIZE + MAP_PADDING + 1 - first_y;
+        let origin = coords.chunk_origin(chunk);
+
+        let mut positions = Vec::with_capacity(size_x * size_y);
+        let mut colors = Vec::with_capacity(size_x * size_y);
+        for local_x in 0..size_x {
+            for local_y in 0..size_y {
+                let cell = coords.chunk_to_cell(chunk, (local_x + first_x, local_y + first_y));
+                let opacity = if self.is_explored(cell.0, cell.1) {
+                    0.
+                } else {
+                    mode.opacity()
+                };
+
+                positions.push((coords.cell_to_world(cell) - origin).extend(0.).to_array());
+                colors.push(color.with_alpha(opacity).to_linear().to_f32_array());
+            }
+        }
+
+        let vertex = |x: usize, y: usize| (x * size_y + y) as u32;
+        let mut indices = Vec::with_capacity((size_x - 1) * (size_y - 1) * 6);
+        for x in 0..size_x - 1 {
+            for y in 0..size_y - 1 {
+                let square = [
+                    vertex(x, y),
+                    vertex(x + 1, y),
+                    vertex(x + 1, y + 1),
+                    vertex(x, y + 1),
+                ];
+                indices.extend([square[0], square[1], square[2]]);
+                indices.extend([square[0], square[2], square[3]]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.insert_indices(Indices::U32(indices));
+
+        return mesh;
+    }
+}
+
+/// Reveals parts of the map, marking the fog over them for rebuilding
+#[derive(SystemParam)]
+pub struct Explorer<'w> {
+    map: ResMut<'w, Map>,
+    coords: Res<'w, TerrainCoords>,
+    fog: ResMut<'w, FogOfWar>,
+}
+
+impl Explorer<'_> {
+    /// Explores everything within `radius` of `center`, both in world units
+    pub fn reveal_circle(&mut self, center: Vec2, radius: f32) {
+        let center = self.coords.world_to_cell_position(center);
+        let radius = radius / self.coords.square_size;
+
+        let explored = self.map.explore_circle(center, radius);
+        self.mark_fog(explored);
+    }
+
+    /// Explores what can be seen within `radius` of `center`, both in world
+    /// units, see [`Map::explore_visible`]
+    pub fn reveal_visible(&mut self, center: Vec2, radius: f32) {
+        let center = self.coords.world_to_cell_position(center);
+        let radius = radius / self.coords.square_size;
+
+        let explored = self.map.explore_visible(center, radius);
+        self.mark_fog(explored);
+    }
+
+    fn mark_fog(&mut self, explored: Vec<(usize, usize)>) {
+        for cell in explored {
+            self.fog
+                .chunks_pending
+                .extend(self.coords.chunks_touching_cell(cell));
+        }
+    }
+}
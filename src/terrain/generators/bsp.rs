@@ -0,0 +1,125 @@
+use bevy::math::URect;
+use rand::{rngs::StdRng, Rng};
+
+use crate::terrain::{resources::Map, tile::Tile};
+
+use super::CaveGenerator;
+
+/// Binary space partitioning, rectangular rooms joined by corridors
+pub struct BspGenerator {
+    /// Leaves smaller than twice this in both directions are not split
+    /// further. Treated as at least 1
+    pub min_leaf_size: u32,
+    /// Minimum number of wall points between a room and the edge of its leaf
+    pub room_padding: u32,
+    pub corridor_radius: usize,
+}
+
+impl Default for BspGenerator {
+    fn default() -> Self {
+        Self {
+            min_leaf_size: 12,
+            room_padding: 2,
+            corridor_radius: 1,
+        }
+    }
+}
+
+impl CaveGenerator for BspGenerator {
+    fn generate(&self, map: &mut Map, rng: &mut StdRng) {
+        let bounds = URect::new(1, 1, map.width as u32 - 1, map.height as u32 - 1);
+        self.split(map, bounds, rng);
+    }
+}
+
+impl BspGenerator {
+    /// Recursively splits `leaf`, carves rooms into the final leaves and
+    /// connects sibling subtrees, returning a point inside the carved area
+    fn split(&self, map: &mut Map, leaf: URect, rng: &mut StdRng) -> (usize, usize) {
+        // a leaf size of 0 would let empty leaves split forever
+        let min_leaf_size = self.min_leaf_size.max(1);
+        let size = leaf.size();
+        let can_split_x = size.x >= min_leaf_size * 2;
+        let can_split_y = size.y >= min_leaf_size * 2;
+
+        if !can_split_x && !can_split_y {
+            return self.carve_room(map, leaf, rng);
+        }
+
+        // prefer cutting across the longer side so leaves stay roughly square
+        let split_x = match (can_split_x, can_split_y) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => size.x >= size.y,
+        };
+
+        let (first, second) = if split_x {
+            let cut = rng.random_range(leaf.min.x + min_leaf_size..=leaf.max.x - min_leaf_size);
+            (
+                URect::new(leaf.min.x, leaf.min.y, cut, leaf.max.y),
+                URect::new(cut, leaf.min.y, leaf.max.x, leaf.max.y),
+            )
+        } else {
+            let cut = rng.random_range(leaf.min.y + min_leaf_size..=leaf.max.y - min_leaf_size);
+            (
+                URect::new(leaf.min.x, leaf.min.y, leaf.max.x, cut),
+                URect::new(leaf.min.x, cut, leaf.max.x, leaf.max.y),
+            )
+        };
+
+        let first = self.split(map, first, rng);
+        let second = self.split(map, second, rng);
+        self.carve_corridor(map, first, second, rng);
+
+        return if rng.random_bool(0.5) { first } else { second };
+    }
+
+    fn carve_room(&self, map: &mut Map, leaf: URect, rng: &mut StdRng) -> (usize, usize) {
+        let padding = self.room_padding.min(leaf.width().saturating_sub(1) / 2);
+        let padding = padding.min(leaf.height().saturating_sub(1) / 2);
+        let inner = URect::new(
+            leaf.min.x + padding,
+            leaf.min.y + padding,
+            leaf.max.x - padding,
+            leaf.max.y - padding,
+        );
+
+        let width = rng.random_range(inner.width().div_ceil(2)..=inner.width());
+        let height = rng.random_range(inner.height().div_ceil(2)..=inner.height());
+        let min_x = rng.random_range(inner.min.x..=inner.max.x - width);
+        let min_y = rng.random_range(inner.min.y..=inner.max.y - height);
+
+        for x in min_x..min_x + width {
+            for y in min_y..min_y + height {
+                if !map.is_border(x as usize, y as usize) {
+                    map.points[x as usize][y as usize] = Tile::Water;
+                }
+            }
+        }
+
+        return ((min_x + width / 2) as usize, (min_y + height / 2) as usize);
+    }
+
+    /// Carves an L shaped corridor, randomly choosing which axis to walk first
+    fn carve_corridor(
+        &self,
+        map: &mut Map,
+        from: (usize, usize),
+        to: (usize, usize),
+        rng: &mut StdRng,
+    ) {
+        let corner = if rng.random_bool(0.5) {
+            (to.0, from.1)
+        } else {
+            (from.0, to.1)
+        };
+
+        for (start, end) in [(from, corner), (corner, to)] {
+            for x in start.0.min(end.0)..=start.0.max(end.0) {
+                for y in start.1.min(end.1)..=start.1.max(end.1) {
+                    map.carve_circle(x, y, self.corridor_radius);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use rand::{distr::Bernoulli, rngs::StdRng};
+
+use crate::terrain::resources::Map;
+
+use super::CaveGenerator;
+
+/// Sebastian Lague's cave generation, random noise smoothed into caverns
+/// by a cellular automaton
+pub struct CellularAutomataGenerator {
+    /// Chance of each point starting out as wall before smoothing
+    pub fill_probability: f64,
+    pub smoothing: usize,
+}
+
+impl Default for CellularAutomataGenerator {
+    fn default() -> Self {
+        Self {
+            fill_probability: 0.48,
+            smoothing: 4,
+        }
+    }
+}
+
+impl CaveGenerator for CellularAutomataGenerator {
+    fn generate(&self, map: &mut Map, rng: &mut StdRng) {
+        let distribution =
+            Bernoulli::new(self.fill_probability).expect("fill probability must be in 0..=1");
+
+        map.random_fill(distribution, rng);
+        for _ in 0..self.smoothing {
+            map.smooth_map();
+        }
+    }
+}
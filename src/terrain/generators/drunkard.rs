@@ -0,0 +1,82 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::terrain::resources::Map;
+
+use super::CaveGenerator;
+
+/// Random walkers that wander around the map carving winding tunnels
+pub struct DrunkardsWalkGenerator {
+    pub walkers: usize,
+    /// Fraction of the map that should be air before the walkers stop
+    pub open_fraction: f32,
+    pub tunnel_radius: usize,
+    /// Chance each step of picking a new direction, lower values give longer straight tunnels
+    pub turn_chance: f64,
+    /// Upper bound on the total steps taken, in case `open_fraction` can't be reached
+    pub max_steps: usize,
+}
+
+impl Default for DrunkardsWalkGenerator {
+    fn default() -> Self {
+        Self {
+            walkers: 6,
+            open_fraction: 0.4,
+            tunnel_radius: 1,
+            turn_chance: 0.25,
+            max_steps: 50_000,
+        }
+    }
+}
+
+impl CaveGenerator for DrunkardsWalkGenerator {
+    fn generate(&self, map: &mut Map, rng: &mut StdRng) {
+        let interior = (map.width - 2) * (map.height - 2);
+        let target = (interior as f32 * self.open_fraction) as usize;
+
+        // keep the walkers far enough from the edge that tunnels never touch the border
+        let margin = self.tunnel_radius + 1;
+        if map.width <= margin * 2 || map.height <= margin * 2 {
+            return;
+        }
+        let (min_x, max_x) = (margin, map.width - 1 - margin);
+        let (min_y, max_y) = (margin, map.height - 1 - margin);
+
+        let walkers = self.walkers.max(1);
+        let steps_per_walker = self.max_steps / walkers;
+        let mut open = 0;
+        let mut air = Vec::new();
+
+        for walker in 0..walkers {
+            // the first walker starts in the middle, the rest branch off existing tunnels
+            let (mut x, mut y) = if walker == 0 || air.is_empty() {
+                (map.width / 2, map.height / 2)
+            } else {
+                air[rng.random_range(0..air.len())]
+            };
+            let mut direction = rng.random_range(0..4);
+
+            for _ in 0..steps_per_walker {
+                if open >= target {
+                    return;
+                }
+
+                let carved = map.carve_circle(x, y, self.tunnel_radius);
+                if carved > 0 {
+                    air.push((x, y));
+                }
+                open += carved;
+
+                if rng.random_bool(self.turn_chance) {
+                    direction = rng.random_range(0..4);
+                }
+
+                match direction {
+                    0 => x = (x + 1).min(max_x),
+                    1 => x = x.saturating_sub(1).max(min_x),
+                    2 => y = (y + 1).min(max_y),
+                    _ => y = y.saturating_sub(1).max(min_y),
+                }
+            }
+        }
+    }
+}
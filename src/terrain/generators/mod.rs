@@ -0,0 +1,80 @@
+use std::sync::Arc;
+
+use bevy::utils::default;
+use rand::rngs::StdRng;
+
+use bsp::BspGenerator;
+use cellular::CellularAutomataGenerator;
+use drunkard::DrunkardsWalkGenerator;
+use noise::NoiseGenerator;
+
+use super::{resources::Map, tile::Tile};
+
+pub mod bsp;
+pub mod cellular;
+pub mod drunkard;
+pub mod noise;
+
+/// A strategy for carving caves out of a [`Map`].
+///
+/// Generators only decide which points are [`Tile::Rock`] and which are
+/// [`Tile::Water`], the map border, region cleanup and the other materials
+/// are handled by [`Map::new`] afterwards.
+pub trait CaveGenerator: Send + Sync {
+    /// Carves a cave into `map`, which starts out completely filled with wall.
+    /// All randomness must come from `rng` so that seeds stay reproducible.
+    fn generate(&self, map: &mut Map, rng: &mut StdRng);
+}
+
+/// Looks up one of the built in generators with its default settings
+pub fn generator_from_name(name: &str) -> Option<Arc<dyn CaveGenerator>> {
+    let generator: Arc<dyn CaveGenerator> = match name {
+        "cellular" => Arc::new(CellularAutomataGenerator::default()),
+        "drunkard" => Arc::new(DrunkardsWalkGenerator::default()),
+        "noise" => Arc::new(NoiseGenerator::default()),
+        "bsp" => Arc::new(BspGenerator::default()),
+        "mixed" => Arc::new(CombinedGenerator::new(vec![
+            Arc::new(CellularAutomataGenerator::default()),
+            Arc::new(DrunkardsWalkGenerator {
+                open_fraction: 0.15,
+                ..default()
+            }),
+        ])),
+        _ => return None,
+    };
+
+    return Some(generator);
+}
+
+/// Runs several generators over the same map and keeps the air carved by any
+/// of them, e.g. drunkard's walk tunnels running through cellular caverns
+pub struct CombinedGenerator {
+    pub generators: Vec<Arc<dyn CaveGenerator>>,
+}
+
+impl CombinedGenerator {
+    pub fn new(generators: Vec<Arc<dyn CaveGenerator>>) -> Self {
+        Self { generators }
+    }
+}
+
+impl CaveGenerator for CombinedGenerator {
+    fn generate(&self, map: &mut Map, rng: &mut StdRng) {
+        let mut combined = vec![vec![Tile::Rock; map.height]; map.width];
+
+        for generator in &self.generators {
+            map.points = vec![vec![Tile::Rock; map.height]; map.width];
+            generator.generate(map, rng);
+
+            for (combined_column, column) in combined.iter_mut().zip(&map.points) {
+                for (combined_point, point) in combined_column.iter_mut().zip(column) {
+                    if !point.is_solid() {
+                        *combined_point = Tile::Water;
+                    }
+                }
+            }
+        }
+
+        map.points = combined;
+    }
+}
@@ -0,0 +1,107 @@
+use rand::{rngs::StdRng, Rng};
+
+use crate::terrain::{resources::Map, tile::Tile};
+
+use super::CaveGenerator;
+
+/// Thresholded fractal value noise, giving blobby open caverns
+pub struct NoiseGenerator {
+    /// Size in points of the largest noise features
+    pub scale: f32,
+    pub octaves: usize,
+    /// How much each octave contributes relative to the previous one
+    pub persistence: f32,
+    /// Points with a noise value above this become wall
+    pub threshold: f32,
+}
+
+impl Default for NoiseGenerator {
+    fn default() -> Self {
+        Self {
+            scale: 24.,
+            octaves: 3,
+            persistence: 0.5,
+            threshold: 0.5,
+        }
+    }
+}
+
+impl CaveGenerator for NoiseGenerator {
+    fn generate(&self, map: &mut Map, rng: &mut StdRng) {
+        let mut noise = vec![vec![0.; map.height]; map.width];
+        let mut amplitude = 1.;
+        let mut total_amplitude = 0.;
+        let mut scale = self.scale.max(1.);
+
+        for _ in 0..self.octaves.max(1) {
+            let lattice = ValueNoise::new(map.width, map.height, scale, rng);
+
+            for (x, column) in noise.iter_mut().enumerate() {
+                for (y, value) in column.iter_mut().enumerate() {
+                    *value += lattice.sample(x, y) * amplitude;
+                }
+            }
+
+            total_amplitude += amplitude;
+            amplitude *= self.persistence;
+            scale = (scale / 2.).max(1.);
+        }
+
+        for (x, column) in noise.iter().enumerate() {
+            for (y, value) in column.iter().enumerate() {
+                map.points[x][y] = if value / total_amplitude > self.threshold {
+                    Tile::Rock
+                } else {
+                    Tile::Water
+                };
+            }
+        }
+    }
+}
+
+/// A grid of random values spaced `scale` points apart, smoothly
+/// interpolated in between
+pub struct ValueNoise {
+    values: Vec<Vec<f32>>,
+    scale: f32,
+}
+
+impl ValueNoise {
+    pub fn new(width: usize, height: usize, scale: f32, rng: &mut StdRng) -> Self {
+        let lattice_width = (width as f32 / scale) as usize + 2;
+        let lattice_height = (height as f32 / scale) as usize + 2;
+
+        let values = (0..lattice_width)
+            .map(|_| (0..lattice_height).map(|_| rng.random::<f32>()).collect())
+            .collect();
+
+        Self { values, scale }
+    }
+
+    /// Noise in 0..1 at the point (`x`, `y`)
+    pub fn sample(&self, x: usize, y: usize) -> f32 {
+        let x = x as f32 / self.scale;
+        let y = y as f32 / self.scale;
+
+        let (cell_x, cell_y) = (x as usize, y as usize);
+        let smooth = |t: f32| t * t * (3. - 2. * t);
+        let (t_x, t_y) = (smooth(x.fract()), smooth(y.fract()));
+
+        let bottom = lerp(
+            self.values[cell_x][cell_y],
+            self.values[cell_x + 1][cell_y],
+            t_x,
+        );
+        let top = lerp(
+            self.values[cell_x][cell_y + 1],
+            self.values[cell_x + 1][cell_y + 1],
+            t_x,
+        );
+
+        return lerp(bottom, top, t_y);
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    return a + (b - a) * t;
+}
@@ -0,0 +1,116 @@
+use rand::{rngs::StdRng, Rng};
+
+use super::{generators::noise::ValueNoise, resources::Map, tile::Tile};
+
+/// Controls how [`Map::distribute_materials`] turns plain rock into the
+/// other solid tiles
+#[derive(Clone)]
+pub struct MaterialDistribution {
+    /// Size in points of the hard rock patches
+    pub hard_rock_scale: f32,
+    /// Noise value above which rock becomes hard rock at the top of the map
+    pub hard_rock_threshold_top: f32,
+    /// Noise value above which rock becomes hard rock at the bottom of the
+    /// map, lower than at the top so that deeper caves are harder to dig
+    pub hard_rock_threshold_bottom: f32,
+    /// How many points of wall below open water settle into sand
+    pub sand_depth: usize,
+    pub ore_veins: usize,
+    pub ore_vein_radius: usize,
+    /// Chance of each point within a vein's radius actually being ore
+    pub ore_density: f64,
+}
+
+impl Default for MaterialDistribution {
+    fn default() -> Self {
+        Self {
+            hard_rock_scale: 12.,
+            hard_rock_threshold_top: 0.7,
+            hard_rock_threshold_bottom: 0.45,
+            sand_depth: 2,
+            ore_veins: 12,
+            ore_vein_radius: 2,
+            ore_density: 0.7,
+        }
+    }
+}
+
+impl Map {
+    /// Replaces the plain rock left by the generator with hard rock, sand
+    /// and ore. Only changes which material a wall is made of, never whether
+    /// a point is solid
+    pub fn distribute_materials(&mut self, materials: &MaterialDistribution, rng: &mut StdRng) {
+        let noise = ValueNoise::new(self.width, self.height, materials.hard_rock_scale, rng);
+
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if self.points[x][y] != Tile::Rock {
+                    continue;
+                }
+
+                let depth = 1. - y as f32 / (self.height - 1) as f32;
+                let threshold = materials.hard_rock_threshold_top
+                    + (materials.hard_rock_threshold_bottom - materials.hard_rock_threshold_top)
+                        * depth;
+
+                if noise.sample(x, y) > threshold {
+                    self.points[x][y] = Tile::HardRock;
+                }
+            }
+        }
+
+        // sand settles on the floor of every open space
+        for x in 0..self.width {
+            for y in 1..self.height {
+                if self.points[x][y].is_solid() || !self.is_solid(x, y - 1) {
+                    continue;
+                }
+
+                for depth in 1..=materials.sand_depth.min(y) {
+                    if !matches!(self.points[x][y - depth], Tile::Rock | Tile::HardRock) {
+                        break;
+                    }
+
+                    self.points[x][y - depth] = Tile::Sand;
+                }
+            }
+        }
+
+        let ore_candidates = (0..self.width)
+            .flat_map(|x| (0..self.height).map(move |y| (x, y)))
+            .filter(|&(x, y)| matches!(self.points[x][y], Tile::Rock | Tile::HardRock))
+            .collect::<Vec<(usize, usize)>>();
+
+        if ore_candidates.is_empty() {
+            return;
+        }
+
+        for _ in 0..materials.ore_veins {
+            let (center_x, center_y) = ore_candidates[rng.random_range(0..ore_candidates.len())];
+            let (center_x, center_y) = (center_x as i32, center_y as i32);
+            let radius = materials.ore_vein_radius as i32;
+
+            for x in center_x - radius..=center_x + radius {
+                for y in center_y - radius..=center_y + radius {
+                    let in_vein = (x - center_x).pow(2) + (y - center_y).pow(2) <= radius * radius;
+                    if !in_vein || !rng.random_bool(materials.ore_density) {
+                        continue;
+                    }
+
+                    if x < 0 || y < 0 {
+                        continue;
+                    }
+
+                    let (x, y) = (x as usize, y as usize);
+                    if !self.is_in_map(x, y) || self.is_border(x, y) {
+                        continue;
+                    }
+
+                    if matches!(self.points[x][y], Tile::Rock | Tile::HardRock) {
+                        self.points[x][y] = Tile::Ore;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,28 +1,108 @@
 use bevy::prelude::*;
-use rand::distr::Bernoulli;
-use resources::{ChunksPendingRebuild, Map};
-use systems::{draw_debug_chunk_borders, draw_on_map, regenerate_chunks, setup_map};
+use coords::TerrainCoords;
+use crater::Crater;
+use events::{MapLoaded, TerrainModified};
+use resources::{
+    ChunkMeshTasks, ChunksPendingRebuild, DigProgress, FogOfWar, Map, TerrainBrush, TerrainConfig,
+    TerrainContours, UnsupportedCells,
+};
+use systems::{
+    apply_chunk_meshes, carve_craters, collapse_loose_tiles, cycle_fog_mode,
+    draw_debug_chunk_borders, draw_debug_contours, draw_dig_cracks, draw_on_map, explode_at_cursor,
+    log_terrain_changes, rebuild_fog, regenerate_chunks, save_or_load_map, setup_map, spawn_fog,
+    stitch_terrain_contours, update_brush,
+};
 
 pub mod components;
 pub mod resources;
 pub mod systems;
 
+pub mod brush;
 pub mod chunk;
+pub mod contour;
+pub mod coords;
+pub mod crater;
+pub mod density;
+pub mod editor;
+pub mod events;
+pub mod fog;
+pub mod generators;
+pub mod materials;
+pub mod placement;
+pub mod raycast;
+pub mod rooms;
+pub mod save;
+pub mod tile;
+pub mod visibility;
 
 pub const SQUARE_SIZE: f32 = 10.;
 
 pub const WATER_COLOR: Color = Color::hsl(230.0, 0.4, 0.3);
 pub const WALL_COLOR: Color = Color::hsl(230.0, 0.1, 0.3);
+pub const SAND_COLOR: Color = Color::hsl(40.0, 0.3, 0.45);
+pub const HARD_ROCK_COLOR: Color = Color::hsl(230.0, 0.1, 0.2);
+pub const ORE_COLOR: Color = Color::hsl(15.0, 0.6, 0.45);
+pub const BEDROCK_COLOR: Color = Color::hsl(230.0, 0.1, 0.1);
+pub const FOG_COLOR: Color = Color::hsl(230.0, 0.3, 0.04);
 
-pub struct TerrainPlugin;
+#[derive(Default)]
+pub struct TerrainPlugin {
+    pub config: TerrainConfig,
+}
+
+impl TerrainPlugin {
+    pub fn new(config: TerrainConfig) -> Self {
+        Self { config }
+    }
+}
 
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Map::new(8, 4, Bernoulli::new(0.48).unwrap(), 4, 50, 500))
+        // a config inserted before the plugin takes priority over the one it was built with
+        let config = app
+            .world()
+            .get_resource::<TerrainConfig>()
+            .cloned()
+            .unwrap_or_else(|| self.config.clone());
+
+        app.insert_resource(Map::from_config(&config))
+            .insert_resource(TerrainCoords::from_config(&config))
+            .insert_resource(config)
             .insert_resource(ChunksPendingRebuild::default())
+            .insert_resource(ChunkMeshTasks::default())
+            .insert_resource(TerrainContours::default())
+            .init_resource::<TerrainBrush>()
+            .init_resource::<DigProgress>()
+            .init_resource::<UnsupportedCells>()
+            .init_resource::<FogOfWar>()
+            .add_event::<TerrainModified>()
+            .add_event::<Crater>()
+            .add_event::<MapLoaded>()
             .add_systems(Startup, setup_map)
+            .add_systems(Startup, spawn_fog)
             .add_systems(Update, draw_debug_chunk_borders)
-            .add_systems(Update, draw_on_map)
-            .add_systems(Update, regenerate_chunks);
+            .add_systems(Update, update_brush)
+            .add_systems(Update, draw_on_map.after(update_brush))
+            .add_systems(Update, explode_at_cursor)
+            .add_systems(Update, carve_craters.after(explode_at_cursor))
+            .add_systems(
+                Update,
+                collapse_loose_tiles.after(draw_on_map).after(carve_craters),
+            )
+            .add_systems(
+                Update,
+                log_terrain_changes.after(draw_on_map).after(carve_craters),
+            )
+            .add_systems(Update, draw_debug_contours)
+            .add_systems(Update, draw_dig_cracks.after(draw_on_map))
+            .add_systems(Update, apply_chunk_meshes)
+            // finished builds are taken first, so an edited chunk's follow
+            // up rebuild starts in the same frame
+            .add_systems(Update, regenerate_chunks.after(apply_chunk_meshes))
+            .add_systems(Update, stitch_terrain_contours.after(apply_chunk_meshes))
+            .add_systems(Update, cycle_fog_mode)
+            // after the explorers of the frame, whichever plugin they are in
+            .add_systems(PostUpdate, rebuild_fog)
+            .add_systems(Update, save_or_load_map.before(regenerate_chunks));
     }
 }
@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, Rng};
+
+use super::resources::Map;
+
+/// A route between two points of the map for something of a given clearance
+pub struct MapPath {
+    pub cells: Vec<(usize, usize)>,
+    /// Number of steps along the path that have wall within the clearance
+    /// radius and would have to be dug through
+    pub dig_cost: usize,
+}
+
+impl Map {
+    /// Whether a circle of radius `clearance` centered on (`x`, `y`) is all air
+    pub fn has_clearance(&self, x: usize, y: usize, clearance: usize) -> bool {
+        return self
+            .footprint(x, y, clearance)
+            .is_some_and(|footprint| footprint.iter().all(|&(x, y)| !self.is_solid(x, y)));
+    }
+
+    /// Finds the path between `from` and `to` that digs through the least
+    /// wall, never passing through the indestructible map border
+    pub fn find_path(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+        clearance: usize,
+    ) -> Option<MapPath> {
+        let mut dig_costs = vec![vec![usize::MAX; self.height]; self.width];
+        let mut parents = vec![vec![None; self.height]; self.width];
+        let mut queued_tiles = VecDeque::new();
+
+        dig_costs[from.0][from.1] = 0;
+        queued_tiles.push_back(from);
+
+        // 0-1 breadth first search, open steps are free and blocked steps cost one
+        while let Some((tile_x, tile_y)) = queued_tiles.pop_front() {
+            if (tile_x, tile_y) == to {
+                break;
+            }
+
+            for offset in [(-1, 0), (1, 0), (0, 1), (0, -1)] {
+                let target_x = tile_x.checked_add_signed(offset.0).unwrap_or_default();
+                let target_y = tile_y.checked_add_signed(offset.1).unwrap_or_default();
+
+                let Some(footprint) = self.footprint(target_x, target_y, clearance) else {
+                    continue;
+                };
+
+                let blocked = footprint.iter().any(|&(x, y)| self.is_solid(x, y));
+                let dig_cost = dig_costs[tile_x][tile_y] + blocked as usize;
+                if dig_cost >= dig_costs[target_x][target_y] {
+                    continue;
+                }
+
+                dig_costs[target_x][target_y] = dig_cost;
+                parents[target_x][target_y] = Some((tile_x, tile_y));
+
+                if blocked {
+                    queued_tiles.push_back((target_x, target_y));
+                } else {
+                    queued_tiles.push_front((target_x, target_y));
+                }
+            }
+        }
+
+        if dig_costs[to.0][to.1] == usize::MAX {
+            return None;
+        }
+
+        let mut cells = vec![to];
+        while let Some(parent) = parents[cells[cells.len() - 1].0][cells[cells.len() - 1].1] {
+            cells.push(parent);
+        }
+        cells.reverse();
+
+        return Some(MapPath {
+            cells,
+            dig_cost: dig_costs[to.0][to.1],
+        });
+    }
+
+    /// Picks a spawn and goal far apart from each other in places with enough
+    /// clearance, returning false if the map has nowhere with enough room
+    pub fn place_endpoints(&mut self, clearance: usize, rng: &mut StdRng) -> bool {
+        let candidates = (0..self.width)
+            .flat_map(|x| (0..self.height).map(move |y| (x, y)))
+            .filter(|&(x, y)| self.has_clearance(x, y, clearance))
+            .collect::<Vec<(usize, usize)>>();
+
+        if candidates.is_empty() {
+            return false;
+        }
+
+        let farthest_from = |from: (usize, usize)| {
+            return *candidates
+                .iter()
+                .max_by_key(|(x, y)| x.abs_diff(from.0).pow(2) + y.abs_diff(from.1).pow(2))
+                .unwrap();
+        };
+
+        // walking to the farthest point twice gets close to the two most distant points
+        let start = candidates[rng.random_range(0..candidates.len())];
+        self.spawn = farthest_from(start);
+        self.goal = farthest_from(self.spawn);
+
+        return true;
+    }
+
+    /// Spreads up to `count` refill points over the places with enough
+    /// clearance, each as far as possible from the spawn, the goal and the
+    /// refill points placed before it
+    pub fn place_refill_points(&mut self, count: usize, clearance: usize) {
+        let candidates = (0..self.width)
+            .flat_map(|x| (0..self.height).map(move |y| (x, y)))
+            .filter(|&(x, y)| self.has_clearance(x, y, clearance))
+            .collect::<Vec<(usize, usize)>>();
+
+        let distance_squared = |a: (usize, usize), b: (usize, usize)| {
+            return a.0.abs_diff(b.0).pow(2) + a.1.abs_diff(b.1).pow(2);
+        };
+
+        let mut taken = vec![self.spawn, self.goal];
+        self.refill_points.clear();
+
+        for _ in 0..count {
+            let Some(&farthest) = candidates
+                .iter()
+                .filter(|&&candidate| !taken.contains(&candidate))
+                .max_by_key(|&&candidate| {
+                    return taken
+                        .iter()
+                        .map(|&other| distance_squared(candidate, other))
+                        .min()
+                        .unwrap_or(usize::MAX);
+                })
+            else {
+                break;
+            };
+
+            taken.push(farthest);
+            self.refill_points.push(farthest);
+        }
+    }
+
+    /// Carves a tunnel from the spawn to the goal if getting there would
+    /// take digging through more than `max_dig_cells`. Returns `false` if
+    /// there is no way to the goal at all, not even by digging
+    pub fn ensure_reachable(&mut self, clearance: usize, max_dig_cells: usize) -> bool {
+        let Some(path) = self.find_path(self.spawn, self.goal, clearance) else {
+            return false;
+        };
+
+        if path.dig_cost <= max_dig_cells {
+            return true;
+        }
+
+        for (x, y) in path.cells {
+            self.carve_circle(x, y, clearance);
+        }
+
+        return true;
+    }
+
+    /// The points within `radius` of (`x`, `y`), or `None` if any of them are
+    /// outside the map or on its border
+    fn footprint(&self, x: usize, y: usize, radius: usize) -> Option<Vec<(usize, usize)>> {
+        if x <= radius
+            || y <= radius
+            || x + radius >= self.width - 1
+            || y + radius >= self.height - 1
+        {
+            return None;
+        }
+
+        let radius = radius as i32;
+        let mut footprint = Vec::new();
+
+        for offset_x in -radius..=radius {
+            for offset_y in -radius..=radius {
+                if offset_x * offset_x + offset_y * offset_y > radius * radius {
+                    continue;
+                }
+
+                footprint.push((
+                    x.checked_add_signed(offset_x as isize).unwrap(),
+                    y.checked_add_signed(offset_y as isize).unwrap(),
+                ));
+            }
+        }
+
+        return Some(footprint);
+    }
+}
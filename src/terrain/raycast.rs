@@ -0,0 +1,329 @@
+use bevy::math::{IVec2, Vec2};
+
+use super::{resources::Map, tile::Tile};
+
+/// Where a ray or a shape met the terrain, measured in cells
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RaycastHit {
+    /// The marching square that was hit, named after its bottom left cell
+    pub square: (usize, usize),
+    /// The solid cell closest to `point`
+    pub cell: (usize, usize),
+    pub tile: Tile,
+    /// Where the wall was touched
+    pub point: Vec2,
+    /// Points away from the wall, into the water
+    pub normal: Vec2,
+    /// How far the ray or the shape's center travelled before the hit
+    pub distance: f32,
+}
+
+impl Map {
+    /// The first wall a ray from `origin` along `direction` runs into within
+    /// `max_distance`, all measured in cells. The ray is tested against the
+    /// same marching squares contour the chunks are meshed from, so it hits
+    /// what is drawn rather than the cell grid. Walls are only hit from the
+    /// water side, a ray starting inside a wall passes out of it
+    pub fn raycast(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<RaycastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        // walks the squares along the ray in order, so the first square
+        // with a hit has the closest one
+        let mut square = origin.floor().as_ivec2();
+        let step = IVec2::new(direction.x.signum() as i32, direction.y.signum() as i32);
+        let boundary_distance = |origin: f32, square: i32, direction: f32| -> f32 {
+            if direction > 0. {
+                return (square as f32 + 1. - origin) / direction;
+            } else if direction < 0. {
+                return (square as f32 - origin) / direction;
+            }
+            return f32::INFINITY;
+        };
+        let mut next_boundary = Vec2::new(
+            boundary_distance(origin.x, square.x, direction.x),
+            boundary_distance(origin.y, square.y, direction.y),
+        );
+        let boundary_step = Vec2::ONE / direction.abs();
+
+        loop {
+            // squares are named after their bottom left cell, so the last
+            // row and column have none
+            let in_map = square.x >= 0
+                && square.y >= 0
+                && (square.x as usize) < self.width - 1
+                && (square.y as usize) < self.height - 1;
+            if !in_map {
+                return None;
+            }
+
+            let square_cell = (square.x as usize, square.y as usize);
+            let hit = self
+                .square_contour(square_cell)
+                .into_iter()
+                .filter_map(|segment| ray_segment_hit(origin, direction, segment))
+                .filter(|&(distance, _)| distance <= max_distance)
+                .min_by(|a, b| a.0.total_cmp(&b.0));
+
+            if let Some((distance, normal)) = hit {
+                return Some(self.hit_at(origin + direction * distance, normal, distance));
+            }
+
+            if next_boundary.min_element() > max_distance {
+                return None;
+            }
+            if next_boundary.x < next_boundary.y {
+                square.x += step.x;
+                next_boundary.x += boundary_step.x;
+            } else {
+                square.y += step.y;
+                next_boundary.y += boundary_step.y;
+            }
+        }
+    }
+
+    /// Whether nothing blocks the straight line from `from` to `to`, both
+    /// measured in cells. Like [`Map::raycast`], walls only block it from
+    /// the water side
+    pub fn line_of_sight(&self, from: Vec2, to: Vec2) -> bool {
+        return self.raycast(from, to - from, from.distance(to)).is_none();
+    }
+
+    /// The first wall a circle of `radius` moving from `origin` along
+    /// `direction` runs into within `max_distance`, all measured in cells.
+    /// A circle already touching a wall it is moving into hits it at a
+    /// distance of 0
+    pub fn circle_cast(
+        &self,
+        origin: Vec2,
+        radius: f32,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> Option<RaycastHit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        let end = origin + direction * max_distance;
+        let segments = self.contour_segments_in(origin.min(end) - radius, origin.max(end) + radius);
+
+        let mut closest: Option<(f32, Vec2, Vec2)> = None;
+        for segment in segments {
+            let Some(hit) = circle_segment_hit(origin, radius, direction, segment) else {
+                continue;
+            };
+            if hit.0 <= max_distance && closest.is_none_or(|closest| hit.0 < closest.0) {
+                closest = Some(hit);
+            }
+        }
+
+        return closest.map(|(distance, point, normal)| self.hit_at(point, normal, distance));
+    }
+
+    /// The solid cell closest to `pos`, out of the corners of the square
+    /// it is in, measured in cells
+    pub fn solid_cell_near(&self, pos: Vec2) -> Option<(usize, usize)> {
+        let corner = pos.floor().max(Vec2::ZERO);
+        let (x, y) = (corner.x as usize, corner.y as usize);
+
+        return [(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]
+            .into_iter()
+            .filter(|&(x, y)| self.is_in_map(x, y) && self.is_solid(x, y))
+            .min_by(|a, b| {
+                let distance = |(x, y): (usize, usize)| Vec2::new(x as f32, y as f32).distance(pos);
+                return distance(*a).total_cmp(&distance(*b));
+            });
+    }
+
+    fn hit_at(&self, point: Vec2, normal: Vec2, distance: f32) -> RaycastHit {
+        let square = point.floor().max(Vec2::ZERO);
+        let square = (
+            (square.x as usize).min(self.width - 2),
+            (square.y as usize).min(self.height - 2),
+        );
+        // the contour only runs between solid cells and water, so there is
+        // always one next to it
+        let cell = self.solid_cell_near(point).unwrap_or(square);
+
+        return RaycastHit {
+            square,
+            cell,
+            tile: self.points[cell.0][cell.1],
+            point,
+            normal,
+            distance,
+        };
+    }
+}
+
+/// Distance the center of a circle moving along a normalized direction
+/// travels before touching a contour segment from the water side, with
+/// the point it touches and the normal pushing it away
+fn circle_segment_hit(
+    origin: Vec2,
+    radius: f32,
+    direction: Vec2,
+    [a, b]: [Vec2; 2],
+) -> Option<(f32, Vec2, Vec2)> {
+    let edge = b - a;
+    let wall_normal = Vec2::new(edge.y, -edge.x).normalize_or_zero();
+    let approach = -direction.dot(wall_normal);
+    if approach <= 0. {
+        return None;
+    }
+
+    let mut closest: Option<(f32, Vec2, Vec2)> = None;
+
+    // the flat side, only reachable from in front of the wall
+    let height = (origin - a).dot(wall_normal);
+    if height >= 0. {
+        let distance = ((height - radius) / approach).max(0.);
+        let center = origin + direction * distance;
+        let along = (center - a).dot(edge) / edge.length_squared();
+        if (0. ..=1.).contains(&along) {
+            closest = Some((distance, a + edge * along, wall_normal));
+        }
+    }
+
+    // the ends, rounded off by the circle
+    for end in [a, b] {
+        let to_end = end - origin;
+        let towards = to_end.dot(direction);
+        let gap = to_end.length_squared() - radius * radius;
+        let discriminant = towards * towards - gap;
+        if discriminant < 0. || towards <= 0. {
+            continue;
+        }
+
+        let distance = (towards - discriminant.sqrt()).max(0.);
+        let normal = (origin + direction * distance - end).normalize_or(wall_normal);
+        if closest.is_none_or(|closest| distance < closest.0) {
+            closest = Some((distance, end, normal));
+        }
+    }
+
+    return closest;
+}
+
+/// Distance along a normalized ray to a contour segment and the segment's
+/// normal, if the ray crosses it from the water side
+fn ray_segment_hit(origin: Vec2, direction: Vec2, [a, b]: [Vec2; 2]) -> Option<(f32, Vec2)> {
+    let edge = b - a;
+    // the wall is on the left of every segment
+    let normal = Vec2::new(edge.y, -edge.x).normalize_or_zero();
+    if direction.dot(normal) >= 0. {
+        return None;
+    }
+
+    let denominator = direction.perp_dot(edge);
+    let to_start = a - origin;
+    let distance = to_start.perp_dot(edge) / denominator;
+    let along = to_start.perp_dot(direction) / denominator;
+
+    if distance < 0. || !(0. ..=1.).contains(&along) {
+        return None;
+    }
+
+    return Some((distance, normal));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Open water with a wall in the middle from (4, 2) up to (4, 4)
+    fn map() -> Map {
+        return Map::from_rows(&[
+            "#########",
+            "#.......#",
+            "#...#...#",
+            "#...#...#",
+            "#...#...#",
+            "#.......#",
+            "#########",
+        ]);
+    }
+
+    #[test]
+    fn ray_hits_the_contour() {
+        let hit = map().raycast(Vec2::new(2., 3.25), Vec2::X, 10.).unwrap();
+
+        assert_eq!(hit.cell, (4, 3));
+        assert_eq!(hit.square, (3, 3));
+        assert_eq!(hit.tile, Tile::Rock);
+        assert!(hit.point.distance(Vec2::new(3.5, 3.25)) < 1e-5);
+        assert!(hit.normal.distance(Vec2::NEG_X) < 1e-5);
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn ray_stops_at_its_distance() {
+        assert_eq!(map().raycast(Vec2::new(2., 3.25), Vec2::X, 1.4), None);
+    }
+
+    #[test]
+    fn ray_leaves_a_wall_it_starts_in() {
+        let hit = map().raycast(Vec2::new(4., 3.), Vec2::X, 10.).unwrap();
+
+        // the far side of the middle wall faces the same way as the ray,
+        // so the first hit is the right edge of the map
+        assert!(hit.normal.distance(Vec2::NEG_X) < 1e-5);
+        assert_eq!(hit.cell, (8, 3));
+    }
+
+    #[test]
+    fn line_of_sight() {
+        let map = map();
+
+        assert!(!map.line_of_sight(Vec2::new(2., 3.), Vec2::new(6., 3.)));
+        assert!(map.line_of_sight(Vec2::new(2., 5.), Vec2::new(6., 5.)));
+        assert!(map.line_of_sight(Vec2::new(2., 1.), Vec2::new(2., 5.)));
+    }
+
+    #[test]
+    fn circle_grazes_the_end_of_a_segment() {
+        // the top of the wall is a corner at (4, 4.5), a circle passing
+        // just above it touches only that corner
+        let hit = map()
+            .circle_cast(Vec2::new(2., 4.9), 0.5, Vec2::X, 10.)
+            .unwrap();
+
+        assert!(hit.point.distance(Vec2::new(4., 4.5)) < 1e-4);
+        assert!((hit.distance - 1.7).abs() < 1e-4);
+        assert!(hit.normal.distance(Vec2::new(-0.6, 0.8)) < 1e-4);
+        assert_eq!(hit.cell, (4, 4));
+    }
+
+    #[test]
+    fn circle_passes_over_the_wall() {
+        let hit = map().circle_cast(Vec2::new(2., 5.), 0.4, Vec2::X, 4.);
+
+        assert_eq!(hit, None);
+    }
+
+    #[test]
+    fn circle_touching_a_wall_hits_it_straight_away() {
+        let hit = map()
+            .circle_cast(Vec2::new(3., 3.25), 0.5, Vec2::X, 10.)
+            .unwrap();
+
+        assert_eq!(hit.distance, 0.);
+        assert!(hit.normal.distance(Vec2::NEG_X) < 1e-5);
+        assert_eq!(hit.cell, (4, 3));
+    }
+
+    #[test]
+    fn circle_hits_the_edge_of_the_map() {
+        // column 1 is water, so the wall is between it and the padding
+        let hit = map()
+            .circle_cast(Vec2::new(2., 3.25), 0.4, Vec2::NEG_X, 10.)
+            .unwrap();
+
+        assert!((hit.distance - 1.1).abs() < 1e-5);
+        assert!(hit.normal.distance(Vec2::X) < 1e-5);
+        assert_eq!(hit.cell, (0, 3));
+    }
+}
@@ -1,71 +1,375 @@
+use std::sync::Arc;
+
 use bevy::{
+    color::{Color, Luminance},
     math::{UVec2, Vec2},
-    prelude::Resource,
+    platform::collections::{HashMap, HashSet},
+    prelude::{warn, Mesh, MouseButton, Resource},
+    tasks::Task,
 };
-use rand::{distr::Bernoulli, prelude::Distribution};
+use rand::{distr::Bernoulli, prelude::Distribution, rngs::StdRng, SeedableRng};
 
 use crate::terrain::SQUARE_SIZE;
 
-use super::chunk::CHUNK_SIZE;
+use super::{
+    brush::{BrushMode, BrushShape},
+    contour::stitch_segments,
+    coords::TerrainCoords,
+    events::CellChange,
+    fog::FogMode,
+    generators::{cellular::CellularAutomataGenerator, CaveGenerator},
+    materials::MaterialDistribution,
+    tile::Tile,
+};
+
+const MAX_GENERATION_ATTEMPTS: u64 = 10;
+
+#[derive(Resource, Clone)]
+pub struct TerrainConfig {
+    pub chunks_x: usize,
+    pub chunks_y: usize,
+    pub generator: Arc<dyn CaveGenerator>,
+    pub min_wall_region_size: usize,
+    pub min_air_region_size: usize,
+    /// Radius of the passages carved to connect isolated caverns
+    pub passage_radius: usize,
+    pub materials: MaterialDistribution,
+    pub square_size: f32,
+    /// Gives the map a density field so walls are drawn with smooth
+    /// contours instead of running through the middle of every edge
+    pub smooth_contours: bool,
+    /// Indexed by [`Tile`], see [`TerrainConfig::tile_color`]
+    pub tile_colors: [Color; Tile::ALL.len()],
+    /// How bright the cave is without any lights, from 0 for pitch black
+    /// to 1 for the full `tile_colors`
+    pub ambient_light: f32,
+    /// Radius around the spawn and goal, and along the path between
+    /// them, that must be free of wall for the submarine to fit
+    pub spawn_clearance: usize,
+    /// How many blocked steps the path from spawn to goal may dig through
+    /// before the map carves a tunnel along it instead
+    pub max_dig_cells: usize,
+    /// Number of places to refill oxygen and battery, there may be fewer
+    /// if the cave is too cramped
+    pub refill_points: usize,
+    /// A random seed is picked when this is `None`
+    pub seed: Option<u64>,
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            chunks_x: 8,
+            chunks_y: 4,
+            generator: Arc::new(CellularAutomataGenerator::default()),
+            min_wall_region_size: 50,
+            min_air_region_size: 500,
+            passage_radius: 1,
+            materials: MaterialDistribution::default(),
+            square_size: SQUARE_SIZE,
+            smooth_contours: true,
+            tile_colors: Tile::ALL.map(Tile::default_color),
+            ambient_light: 0.4,
+            spawn_clearance: 3,
+            max_dig_cells: 40,
+            refill_points: 4,
+            seed: None,
+        }
+    }
+}
+
+impl TerrainConfig {
+    /// The color a tile is drawn with, dimmed by the ambient light
+    pub fn tile_color(&self, tile: Tile) -> Color {
+        let color = self.tile_colors[tile as usize];
+        return color.with_luminance(color.luminance() * self.ambient_light);
+    }
+}
+
+/// The mouse brush used to edit the terrain
+#[derive(Resource, Clone)]
+pub struct TerrainBrush {
+    pub shape: BrushShape,
+    /// Radius in cells
+    pub radius: f32,
+    /// Tile the brush fills water with
+    pub tile: Tile,
+    pub remove_button: MouseButton,
+    pub add_button: MouseButton,
+    /// What the buttons held this frame make the brush do
+    pub action: Option<BrushMode>,
+    /// Where the current stroke was stamped last, in cells
+    pub last_stamp: Option<Vec2>,
+}
+
+impl Default for TerrainBrush {
+    fn default() -> Self {
+        return Self {
+            shape: BrushShape::Circle,
+            radius: 1.5,
+            tile: Tile::Rock,
+            remove_button: MouseButton::Left,
+            add_button: MouseButton::Right,
+            action: None,
+            last_stamp: None,
+        };
+    }
+}
+
+/// How the unexplored parts of the map are covered
+#[derive(Resource, Default, Clone)]
+pub struct FogOfWar {
+    pub mode: FogMode,
+    /// Chunks whose fog changed since it was last built
+    pub chunks_pending: HashSet<UVec2>,
+}
+
+/// Cells that turned into water since loose tiles last fell, the tile
+/// above each may have lost its support
+#[derive(Resource, Default, Clone)]
+pub struct UnsupportedCells {
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// How far along breaking every partly mined cell is, from 0 to 1
+#[derive(Resource, Default, Clone)]
+pub struct DigProgress {
+    pub cells: HashMap<(usize, usize), f32>,
+}
 
 #[derive(Resource, Default, Clone)]
 pub struct ChunksPendingRebuild {
-    pub chunks: Vec<UVec2>,
+    pub chunks: HashSet<UVec2>,
+}
+
+/// Everything rebuilt for a chunk when it changes
+pub struct ChunkBuild {
+    /// One mesh for each layer in [`Tile::SOLID`]
+    pub meshes: [(Tile, Mesh); Tile::SOLID.len()],
+    /// The outline of the walls in world space, see
+    /// [`generate_contours`](super::chunk::Chunk::generate_contours)
+    pub contours: Vec<[Vec2; 2]>,
+}
+
+/// Chunks being rebuilt in the background
+#[derive(Resource, Default)]
+pub struct ChunkMeshTasks {
+    /// Every build still running, with the chunk and generation it is for
+    pub tasks: Vec<(UVec2, u64, Task<ChunkBuild>)>,
+    /// The generation of each chunk, counting up every time a build is
+    /// started for it. Only a build of the current generation is applied,
+    /// older ones finished after it would show an outdated chunk
+    pub generations: HashMap<UVec2, u64>,
+}
+
+/// The outline of the walls, kept up to date as chunks are rebuilt
+#[derive(Resource, Default)]
+pub struct TerrainContours {
+    /// World space segments of every chunk, each with the wall on its left
+    pub chunk_segments: HashMap<UVec2, Vec<[Vec2; 2]>>,
+    /// The segments of all chunks joined into polylines across chunk
+    /// borders. Closed loops repeat their first point at the end
+    pub polylines: Vec<Vec<Vec2>>,
+    /// Set when segments changed since the polylines were last stitched
+    pub needs_stitching: bool,
+}
+
+impl TerrainContours {
+    pub fn set_chunk(&mut self, chunk_position: UVec2, segments: Vec<[Vec2; 2]>) {
+        self.chunk_segments.insert(chunk_position, segments);
+        self.needs_stitching = true;
+    }
+
+    /// Rebuilds the polylines from the segments of every chunk
+    pub fn stitch(&mut self, square_size: f32) {
+        // neighbouring chunks compute shared end points with different offsets,
+        // so they only match up to rounding
+        self.polylines =
+            stitch_segments(self.chunk_segments.values().flatten(), square_size / 1000.);
+        self.needs_stitching = false;
+    }
 }
 
 #[derive(Resource, Clone)]
 pub struct Map {
-    pub points: Vec<Vec<bool>>,
+    pub points: Vec<Vec<Tile>>,
     pub width: usize,
     pub height: usize,
+    /// The seed the map was generated from, the same seed and parameters
+    /// will always produce the same points
+    pub seed: u64,
+    /// Where the submarine starts
+    pub spawn: (usize, usize),
+    /// Where the submarine has to get to
+    pub goal: (usize, usize),
+    /// Where the submarine can top up its oxygen and battery
+    pub refill_points: Vec<(usize, usize)>,
+    /// Optional per point density used to smooth the wall contours, see
+    /// [`Map::generate_density`]. Always kept in agreement with `points`
+    pub density: Option<Vec<Vec<f32>>>,
+    /// Which points the player has seen, see [`Map::explore_circle`]
+    pub explored: Vec<Vec<bool>>,
 }
 
 impl Map {
-    pub fn new(
-        chunk_x: usize,
-        chunk_y: usize,
-        distribution: Bernoulli,
-        smoothing: usize,
-        min_wall_region_size: usize,
-        min_air_region_size: usize,
-    ) -> Self {
-        let width = chunk_x * CHUNK_SIZE + 2;
-        let height = chunk_y * CHUNK_SIZE + 2;
+    /// Generates a map from `seed`. When the cave has no room for a spawn,
+    /// or no way from it to the goal, the following seeds are tried
+    /// instead, so [`Map::seed`] is always the seed that actually produced
+    /// the map. If none of them work the clearance is lowered, and as a
+    /// last resort a passage is carved between two corners of the map
+    pub fn new(config: &TerrainConfig, seed: u64) -> Self {
+        for attempt in 0..MAX_GENERATION_ATTEMPTS {
+            let seed = seed.wrapping_add(attempt);
+            if let Some(map_gen) = Self::generate(config, seed, config.spawn_clearance) {
+                return map_gen;
+            }
+        }
+
+        for clearance in (0..config.spawn_clearance).rev() {
+            warn!("no room for a reachable spawn and goal, lowering the clearance to {clearance}");
+            if let Some(map_gen) = Self::generate(config, seed, clearance) {
+                return map_gen;
+            }
+        }
+
+        warn!("no room for a spawn and goal at all, carving a passage between them");
+        let (mut map_gen, mut rng) = Self::generate_cave(config, seed);
+        // as far into opposite corners as the clearance allows
+        let inset = config.spawn_clearance + 1;
+        map_gen.spawn = (inset.min(map_gen.width - 2), inset.min(map_gen.height - 2));
+        map_gen.goal = (
+            (map_gen.width - 1).saturating_sub(inset).max(1),
+            (map_gen.height - 1).saturating_sub(inset).max(1),
+        );
+        // with no clearance every point off the border can be walked to
+        let path = map_gen
+            .find_path(map_gen.spawn, map_gen.goal, 0)
+            .expect("the spawn and goal are off the border");
+        for (x, y) in path.cells {
+            map_gen.carve_circle(x, y, config.spawn_clearance);
+        }
+        map_gen.finish(config, config.spawn_clearance, &mut rng);
+
+        return map_gen;
+    }
+
+    /// The cave `seed` generates, with its border filled and its caverns
+    /// connected, but no spawn or goal yet
+    fn generate_cave(config: &TerrainConfig, seed: u64) -> (Self, StdRng) {
+        let TerrainCoords { width, height, .. } = TerrainCoords::from_config(config);
 
         let mut map_gen = Self {
-            points: vec![vec![false; height]; width],
+            points: vec![vec![Tile::Rock; height]; width],
             width,
             height,
+            seed,
+            spawn: (0, 0),
+            goal: (0, 0),
+            refill_points: Vec::new(),
+            density: None,
+            explored: vec![vec![false; height]; width],
         };
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        config.generator.generate(&mut map_gen, &mut rng);
+        map_gen.fill_border();
+
+        map_gen.clean_map(config.min_wall_region_size, config.min_air_region_size);
+        map_gen.connect_regions(config.passage_radius);
+
+        return (map_gen, rng);
+    }
+
+    /// Generates the map for `seed` with a spawn and goal reachable with
+    /// `clearance`, or `None` if the cave has no room for them
+    fn generate(config: &TerrainConfig, seed: u64, clearance: usize) -> Option<Self> {
+        let (mut map_gen, mut rng) = Self::generate_cave(config, seed);
 
-        map_gen.clean_map(min_wall_region_size, min_air_region_size);
+        if !map_gen.place_endpoints(clearance, &mut rng) {
+            return None;
+        }
+        if !map_gen.ensure_reachable(clearance, config.max_dig_cells) {
+            return None;
+        }
+        map_gen.finish(config, clearance, &mut rng);
 
-        map_gen.random_fill(distribution);
-        for _ in 0..smoothing {
-            map_gen.smooth_map();
+        return Some(map_gen);
+    }
+
+    /// Places the refill points and materials once the spawn and goal are in
+    fn finish(&mut self, config: &TerrainConfig, clearance: usize, rng: &mut StdRng) {
+        self.place_refill_points(config.refill_points, clearance);
+        self.distribute_materials(&config.materials, rng);
+        if config.smooth_contours {
+            self.generate_density();
         }
+    }
 
-        map_gen.clean_map(min_wall_region_size, min_air_region_size);
+    pub fn from_config(config: &TerrainConfig) -> Self {
+        return Self::new(config, config.seed.unwrap_or_else(rand::random));
+    }
 
-        return map_gen;
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        return self.points[x][y].is_solid();
     }
 
-    pub fn world_space_to_index(&self, pos: Vec2) -> Option<(usize, usize)> {
-        // pretty confident this + 8.5 thing has something to do with the
-        // padding on the edges of the map
-        let pos = (
-            (pos.x / SQUARE_SIZE + 8.5) as usize,
-            (pos.y / SQUARE_SIZE + 8.5) as usize,
-        );
+    /// Changes the tile at (`x`, `y`), keeping the density in agreement
+    pub fn set_tile(&mut self, x: usize, y: usize, tile: Tile) {
+        self.points[x][y] = tile;
 
-        if pos.0 == 0 || pos.0 >= self.width || pos.1 == 0 || pos.1 >= self.height {
-            return None;
+        if let Some(density) = &mut self.density {
+            density[x][y] = if tile.is_solid() { 1. } else { 0. };
         }
+    }
 
-        return Some(pos);
+    pub fn is_border(&self, x: usize, y: usize) -> bool {
+        return x == 0 || x == self.width - 1 || y == 0 || y == self.height - 1;
     }
 
-    fn smooth_map(&mut self) {
+    fn fill_border(&mut self) {
+        for x in 0..self.width {
+            for y in 0..self.height {
+                if self.is_border(x, y) {
+                    self.points[x][y] = Tile::Bedrock;
+                }
+            }
+        }
+    }
+
+    /// Turns every non border point within `radius` of (`x`, `y`) into air,
+    /// returning how many points were wall before
+    pub fn carve_circle(&mut self, x: usize, y: usize, radius: usize) -> usize {
+        let mut carved = 0;
+        let radius = radius as i32;
+
+        for offset_x in -radius..=radius {
+            for offset_y in -radius..=radius {
+                if offset_x * offset_x + offset_y * offset_y > radius * radius {
+                    continue;
+                }
+
+                let target_x = x as i32 + offset_x;
+                let target_y = y as i32 + offset_y;
+                if target_x < 0 || target_y < 0 {
+                    continue;
+                }
+
+                let (target_x, target_y) = (target_x as usize, target_y as usize);
+                if !self.is_in_map(target_x, target_y) || self.is_border(target_x, target_y) {
+                    continue;
+                }
+
+                if self.points[target_x][target_y].is_solid() {
+                    self.points[target_x][target_y] = Tile::Water;
+                    carved += 1;
+                }
+            }
+        }
+
+        return carved;
+    }
+
+    pub fn smooth_map(&mut self) {
         for x in 0..self.width {
             for y in 0..self.height {
                 let neighbors = self.get_nieghbor_wall_count(x as i32, y as i32);
@@ -75,9 +379,9 @@ impl Map {
                 }
 
                 if neighbors > 4 {
-                    self.points[x][y] = true;
+                    self.points[x][y] = Tile::Rock;
                 } else if neighbors < 4 {
-                    self.points[x][y] = false;
+                    self.points[x][y] = Tile::Water;
                 }
             }
         }
@@ -92,7 +396,7 @@ impl Map {
             }
 
             for (x, y) in region {
-                self.points[x][y] = false;
+                self.points[x][y] = Tile::Water;
             }
         }
 
@@ -104,7 +408,7 @@ impl Map {
             }
 
             for (x, y) in region {
-                self.points[x][y] = true;
+                self.points[x][y] = Tile::Rock;
             }
         }
     }
@@ -125,7 +429,7 @@ impl Map {
             if !self.is_in_map(*x as usize, *y as usize) {
                 true
             } else {
-                self.points[*x as usize][*y as usize]
+                self.points[*x as usize][*y as usize].is_solid()
             }
         })
         .count() as i32;
@@ -135,7 +439,7 @@ impl Map {
         let mut contiguous_tiles = Vec::new();
         let mut queued_tiles = Vec::new();
         let mut viewed_tiles = vec![vec![false; self.height]; self.width];
-        let target_tile_type = self.points[start_x][start_y];
+        let target_tile_type = self.points[start_x][start_y].is_solid();
 
         queued_tiles.push((start_x, start_y));
         viewed_tiles[start_x][start_y] = true;
@@ -152,7 +456,7 @@ impl Map {
                     continue;
                 }
 
-                if self.points[target_x][target_y] != target_tile_type {
+                if self.points[target_x][target_y].is_solid() != target_tile_type {
                     continue;
                 }
 
@@ -172,13 +476,14 @@ impl Map {
         return contiguous_tiles;
     }
 
-    fn get_regions(&self, tile_type: bool) -> Vec<Vec<(usize, usize)>> {
+    /// Groups the solid or non solid points into 4-connected regions
+    pub fn get_regions(&self, solid: bool) -> Vec<Vec<(usize, usize)>> {
         let mut regions = Vec::new();
         let mut viewed_tiles = vec![vec![false; self.height]; self.width];
 
         for x in 0..self.width {
             for y in 0..self.height {
-                if self.points[x][y] != tile_type {
+                if self.points[x][y].is_solid() != solid {
                     continue;
                 }
 
@@ -199,19 +504,83 @@ impl Map {
         return regions;
     }
 
-    fn is_in_map(&self, x: usize, y: usize) -> bool {
+    /// Drops every loose tile sitting right above one of `cells` by one cell
+    /// if that cell is water. Returns every cell that changed
+    pub fn collapse_loose_tiles(&mut self, cells: &[(usize, usize)]) -> Vec<CellChange> {
+        let mut changed = Vec::new();
+
+        for &(x, y) in cells {
+            let above = y + 1;
+            if above >= self.height || self.is_solid(x, y) || self.is_border(x, y) {
+                continue;
+            }
+
+            let tile = self.points[x][above];
+            if !tile.is_loose() {
+                continue;
+            }
+
+            self.set_tile(x, y, tile);
+            self.set_tile(x, above, Tile::Water);
+            changed.push(CellChange {
+                cell: (x, y),
+                previous: Tile::Water,
+                new: tile,
+            });
+            changed.push(CellChange {
+                cell: (x, above),
+                previous: tile,
+                new: Tile::Water,
+            });
+        }
+
+        return changed;
+    }
+
+    pub fn is_in_map(&self, x: usize, y: usize) -> bool {
         return !(x >= self.width || y >= self.height);
     }
 
-    fn random_fill(&mut self, distribution: Bernoulli) {
+    pub fn random_fill(&mut self, distribution: Bernoulli, rng: &mut StdRng) {
         for x in 0..self.width {
             for y in 0..self.height {
                 if x == 0 || x == self.width - 1 || y == 0 || y == self.height - 1 {
-                    self.points[x][y] = true;
+                    self.points[x][y] = Tile::Bedrock;
+                } else if distribution.sample(rng) {
+                    self.points[x][y] = Tile::Rock;
                 } else {
-                    self.points[x][y] = distribution.sample(&mut rand::rng());
+                    self.points[x][y] = Tile::Water;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+impl Map {
+    /// A small map for tests drawn as text, `#` for rock and anything else
+    /// for water, with the first row at the top
+    pub fn from_rows(rows: &[&str]) -> Self {
+        let (width, height) = (rows[0].len(), rows.len());
+        let mut points = vec![vec![Tile::Water; height]; width];
+        for (row, line) in rows.iter().enumerate() {
+            for (x, character) in line.chars().enumerate() {
+                if character == '#' {
+                    points[x][height - 1 - row] = Tile::Rock;
                 }
             }
         }
+
+        return Self {
+            points,
+            width,
+            height,
+            seed: 0,
+            spawn: (1, 1),
+            goal: (1, 1),
+            refill_points: Vec::new(),
+            density: None,
+            explored: vec![vec![false; height]; width],
+        };
     }
 }
@@ -0,0 +1,199 @@
+use std::cmp::Reverse;
+
+use super::resources::Map;
+
+/// A connected region of air left over after the map has been cleaned
+struct Room {
+    /// Air tiles that border a wall, passages are always carved between these
+    edge_tiles: Vec<(usize, usize)>,
+    size: usize,
+    connected_rooms: Vec<usize>,
+}
+
+impl Room {
+    fn new(map: &Map, tiles: Vec<(usize, usize)>) -> Self {
+        let edge_tiles = tiles
+            .iter()
+            .copied()
+            .filter(|&(x, y)| {
+                [(-1, 0), (1, 0), (0, 1), (0, -1)]
+                    .iter()
+                    .any(|(offset_x, offset_y)| {
+                        let target_x = x.checked_add_signed(*offset_x).unwrap_or_default();
+                        let target_y = y.checked_add_signed(*offset_y).unwrap_or_default();
+
+                        map.is_in_map(target_x, target_y) && map.is_solid(target_x, target_y)
+                    })
+            })
+            .collect();
+
+        Self {
+            edge_tiles,
+            size: tiles.len(),
+            connected_rooms: Vec::new(),
+        }
+    }
+}
+
+/// The closest pair of edge tiles between two rooms
+struct Connection {
+    room_a: usize,
+    room_b: usize,
+    tile_a: (usize, usize),
+    tile_b: (usize, usize),
+    distance: usize,
+}
+
+impl Map {
+    /// Carves passages between the air regions of the map until every one of
+    /// them can be reached from the largest, which becomes the main room
+    pub fn connect_regions(&mut self, passage_radius: usize) {
+        let mut rooms = self
+            .get_regions(false)
+            .into_iter()
+            .map(|tiles| Room::new(self, tiles))
+            .collect::<Vec<Room>>();
+
+        if rooms.len() < 2 {
+            return;
+        }
+
+        rooms.sort_by_key(|room| Reverse(room.size));
+
+        // first give every room a passage to its nearest neighbour, this keeps
+        // the passages short and makes the cave feel less like a tree
+        for room in 0..rooms.len() {
+            if !rooms[room].connected_rooms.is_empty() {
+                continue;
+            }
+
+            let others = (0..rooms.len())
+                .filter(|other| *other != room)
+                .collect::<Vec<usize>>();
+            if let Some(connection) = closest_connection(&rooms, &[room], &others) {
+                self.create_passage(&mut rooms, connection, passage_radius);
+            }
+        }
+
+        // then keep joining the closest unreachable room to the main room's
+        // network until nothing is left out
+        loop {
+            let accessible = accessible_from_main(&rooms);
+            let (reachable, unreachable): (Vec<usize>, Vec<usize>) =
+                (0..rooms.len()).partition(|room| accessible[*room]);
+
+            if unreachable.is_empty() {
+                break;
+            }
+
+            let Some(connection) = closest_connection(&rooms, &unreachable, &reachable) else {
+                break;
+            };
+            self.create_passage(&mut rooms, connection, passage_radius);
+        }
+    }
+
+    fn create_passage(
+        &mut self,
+        rooms: &mut [Room],
+        connection: Connection,
+        passage_radius: usize,
+    ) {
+        rooms[connection.room_a]
+            .connected_rooms
+            .push(connection.room_b);
+        rooms[connection.room_b]
+            .connected_rooms
+            .push(connection.room_a);
+
+        for (x, y) in line(connection.tile_a, connection.tile_b) {
+            self.carve_circle(x, y, passage_radius);
+        }
+    }
+}
+
+fn closest_connection(rooms: &[Room], from: &[usize], to: &[usize]) -> Option<Connection> {
+    let mut best: Option<Connection> = None;
+
+    for &room_a in from {
+        for &room_b in to {
+            if rooms[room_a].connected_rooms.contains(&room_b) {
+                continue;
+            }
+
+            for &tile_a in &rooms[room_a].edge_tiles {
+                for &tile_b in &rooms[room_b].edge_tiles {
+                    let distance =
+                        tile_a.0.abs_diff(tile_b.0).pow(2) + tile_a.1.abs_diff(tile_b.1).pow(2);
+
+                    if best.as_ref().is_some_and(|best| best.distance <= distance) {
+                        continue;
+                    }
+
+                    best = Some(Connection {
+                        room_a,
+                        room_b,
+                        tile_a,
+                        tile_b,
+                        distance,
+                    });
+                }
+            }
+        }
+    }
+
+    return best;
+}
+
+/// Flood fills the room graph from the main room, which is always room 0
+fn accessible_from_main(rooms: &[Room]) -> Vec<bool> {
+    let mut accessible = vec![false; rooms.len()];
+    let mut queued_rooms = vec![0];
+    accessible[0] = true;
+
+    while let Some(room) = queued_rooms.pop() {
+        for &connected in &rooms[room].connected_rooms {
+            if accessible[connected] {
+                continue;
+            }
+
+            accessible[connected] = true;
+            queued_rooms.push(connected);
+        }
+    }
+
+    return accessible;
+}
+
+/// Bresenham's line between two tiles, including both ends
+fn line(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (from.0 as i32, from.1 as i32);
+    let (end_x, end_y) = (to.0 as i32, to.1 as i32);
+
+    let delta_x = (end_x - x).abs();
+    let delta_y = -(end_y - y).abs();
+    let step_x = if x < end_x { 1 } else { -1 };
+    let step_y = if y < end_y { 1 } else { -1 };
+    let mut error = delta_x + delta_y;
+
+    let mut points = Vec::new();
+    loop {
+        points.push((x as usize, y as usize));
+
+        if x == end_x && y == end_y {
+            break;
+        }
+
+        let doubled_error = error * 2;
+        if doubled_error >= delta_y {
+            error += delta_y;
+            x += step_x;
+        }
+        if doubled_error <= delta_x {
+            error += delta_x;
+            y += step_y;
+        }
+    }
+
+    return points;
+}
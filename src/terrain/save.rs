@@ -0,0 +1,265 @@
+use std::io::{self, Read, Write};
+
+use super::{resources::Map, tile::Tile};
+
+/// Start of every saved map
+const MAGIC: &[u8; 4] = b"SUBM";
+/// Bumped whenever the layout below changes
+const VERSION: u8 = 1;
+
+impl Map {
+    /// Writes the map in a little endian binary format, the explored
+    /// cells included, so it can be read back with [`Map::load`]
+    pub fn save(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        write_u32(writer, self.width as u32)?;
+        write_u32(writer, self.height as u32)?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        write_cell(writer, self.spawn)?;
+        write_cell(writer, self.goal)?;
+
+        write_u32(writer, self.refill_points.len() as u32)?;
+        for &cell in self.refill_points.iter() {
+            write_cell(writer, cell)?;
+        }
+
+        // tiles are stored as their position in `Tile::ALL`
+        for column in self.points.iter() {
+            let tiles: Vec<u8> = column.iter().map(|tile| *tile as u8).collect();
+            writer.write_all(&tiles)?;
+        }
+
+        match &self.density {
+            Some(density) => {
+                writer.write_all(&[1])?;
+                for value in density.iter().flatten() {
+                    writer.write_all(&value.to_le_bytes())?;
+                }
+            }
+            None => writer.write_all(&[0])?,
+        }
+
+        for column in self.explored.iter() {
+            let explored: Vec<u8> = column.iter().map(|explored| *explored as u8).collect();
+            writer.write_all(&explored)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Reads a map written by [`Map::save`]. Maps of any other size than
+    /// `width` by `height` cells are rejected before anything is allocated
+    /// for them, so a corrupt file can not ask for more memory than the
+    /// running map uses
+    pub fn load(reader: &mut impl Read, width: usize, height: usize) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a saved map"));
+        }
+
+        let version = read_u8(reader)?;
+        if version != VERSION {
+            return Err(invalid_data(&format!("unsupported map version {version}")));
+        }
+
+        let saved_width = read_u32(reader)? as usize;
+        let saved_height = read_u32(reader)? as usize;
+        if (saved_width, saved_height) != (width, height) {
+            return Err(invalid_data(&format!(
+                "the saved map is {saved_width}x{saved_height} cells, expected {width}x{height}"
+            )));
+        }
+        let mut seed = [0; 8];
+        reader.read_exact(&mut seed)?;
+        let seed = u64::from_le_bytes(seed);
+        let spawn = read_cell(reader)?;
+        let goal = read_cell(reader)?;
+
+        let refill_count = read_u32(reader)?;
+        let refill_points = (0..refill_count)
+            .map(|_| read_cell(reader))
+            .collect::<io::Result<Vec<(usize, usize)>>>()?;
+
+        let mut points = Vec::with_capacity(width);
+        for _ in 0..width {
+            let mut column = vec![0; height];
+            reader.read_exact(&mut column)?;
+            let column = column
+                .into_iter()
+                .map(|index| {
+                    return Tile::ALL
+                        .get(index as usize)
+                        .copied()
+                        .ok_or_else(|| invalid_data(&format!("unknown tile {index}")));
+                })
+                .collect::<io::Result<Vec<Tile>>>()?;
+            points.push(column);
+        }
+
+        let density = match read_u8(reader)? {
+            0 => None,
+            _ => {
+                let mut density = vec![vec![0.; height]; width];
+                for value in density.iter_mut().flatten() {
+                    let mut bytes = [0; 4];
+                    reader.read_exact(&mut bytes)?;
+                    *value = f32::from_le_bytes(bytes);
+                }
+                Some(density)
+            }
+        };
+
+        let mut explored = Vec::with_capacity(width);
+        for _ in 0..width {
+            let mut column = vec![0; height];
+            reader.read_exact(&mut column)?;
+            explored.push(column.into_iter().map(|explored| explored != 0).collect());
+        }
+
+        let in_map = |(x, y): (usize, usize)| x < width && y < height;
+        if !in_map(spawn) || !in_map(goal) || !refill_points.iter().copied().all(in_map) {
+            return Err(invalid_data("a point of interest is outside the map"));
+        }
+
+        return Ok(Self {
+            points,
+            width,
+            height,
+            seed,
+            spawn,
+            goal,
+            refill_points,
+            density,
+            explored,
+        });
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, message);
+}
+
+fn write_u32(writer: &mut impl Write, value: u32) -> io::Result<()> {
+    return writer.write_all(&value.to_le_bytes());
+}
+
+fn write_cell(writer: &mut impl Write, (x, y): (usize, usize)) -> io::Result<()> {
+    write_u32(writer, x as u32)?;
+    return write_u32(writer, y as u32);
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+    return Ok(bytes[0]);
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    return Ok(u32::from_le_bytes(bytes));
+}
+
+fn read_cell(reader: &mut impl Read) -> io::Result<(usize, usize)> {
+    return Ok((read_u32(reader)? as usize, read_u32(reader)? as usize));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved_map() -> Map {
+        let mut map = Map::from_rows(&["#####", "#..##", "#...#", "#####"]);
+        map.points[3][1] = Tile::Ore;
+        map.seed = 42;
+        map.spawn = (1, 1);
+        map.goal = (3, 1);
+        map.refill_points = vec![(2, 1), (1, 2)];
+        map.density = Some(vec![vec![0.25; map.height]; map.width]);
+        map.explored[1][1] = true;
+        map.explored[2][2] = true;
+        return map;
+    }
+
+    fn save(map: &Map) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        map.save(&mut bytes).unwrap();
+        return bytes;
+    }
+
+    #[test]
+    fn round_trip() {
+        let map = saved_map();
+        let loaded = Map::load(&mut save(&map).as_slice(), map.width, map.height).unwrap();
+
+        assert_eq!(loaded.points, map.points);
+        assert_eq!(loaded.density, map.density);
+        assert_eq!(loaded.explored, map.explored);
+        assert_eq!(loaded.seed, map.seed);
+        assert_eq!(loaded.spawn, map.spawn);
+        assert_eq!(loaded.goal, map.goal);
+        assert_eq!(loaded.refill_points, map.refill_points);
+    }
+
+    #[test]
+    fn round_trip_without_density() {
+        let mut map = saved_map();
+        map.density = None;
+        let loaded = Map::load(&mut save(&map).as_slice(), map.width, map.height).unwrap();
+
+        assert_eq!(loaded.points, map.points);
+        assert_eq!(loaded.density, None);
+        assert_eq!(loaded.explored, map.explored);
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let map = saved_map();
+        let mut bytes = save(&map);
+        bytes[0] = b'X';
+
+        assert!(Map::load(&mut bytes.as_slice(), map.width, map.height).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_version() {
+        let map = saved_map();
+        let mut bytes = save(&map);
+        bytes[MAGIC.len()] = VERSION + 1;
+
+        assert!(Map::load(&mut bytes.as_slice(), map.width, map.height).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_tile() {
+        let mut map = saved_map();
+        map.density = None;
+        let mut bytes = save(&map);
+        // the tiles come before the density flag and the explored cells
+        let cells = map.width * map.height;
+        let first_tile = bytes.len() - cells - 1 - cells;
+        bytes[first_tile] = Tile::ALL.len() as u8;
+
+        assert!(Map::load(&mut bytes.as_slice(), map.width, map.height).is_err());
+    }
+
+    #[test]
+    fn rejects_other_sizes() {
+        let map = saved_map();
+        let bytes = save(&map);
+
+        assert!(Map::load(&mut bytes.as_slice(), map.width + 1, map.height).is_err());
+        assert!(Map::load(&mut bytes.as_slice(), map.width, usize::MAX).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let map = saved_map();
+        let bytes = save(&map);
+
+        assert!(Map::load(&mut &bytes[..bytes.len() - 1], map.width, map.height).is_err());
+    }
+}
@@ -1,151 +1,484 @@
-use bevy::prelude::*;
+use std::{
+    f32::consts::PI,
+    fs::File,
+    io::{BufReader, BufWriter},
+};
+
+use bevy::{
+    platform::collections::HashMap,
+    prelude::*,
+    tasks::{block_on, poll_once, AsyncComputeTaskPool},
+};
 
-use crate::terrain::{components::TerrainMesh, WALL_COLOR};
+use crate::terrain::components::{FogMesh, TerrainMesh};
 
 use super::{
-    chunk::{ChunkMap, CHUNK_SIZE},
-    resources::{ChunksPendingRebuild, Map},
-    SQUARE_SIZE, WATER_COLOR,
+    brush::{BrushMode, BrushShape},
+    chunk::Chunk,
+    coords::TerrainCoords,
+    crater::Crater,
+    editor::TerrainEditor,
+    events::{MapLoaded, TerrainChangeCause, TerrainModified},
+    fog::FogMode,
+    resources::{
+        ChunkBuild, ChunkMeshTasks, ChunksPendingRebuild, DigProgress, FogOfWar, Map, TerrainBrush,
+        TerrainConfig, TerrainContours, UnsupportedCells,
+    },
+    tile::Tile,
+    FOG_COLOR,
 };
 
+/// Largest gap between two stamps of a brush stroke, in cells
+const STROKE_SPACING: f32 = 0.5;
+const MIN_BRUSH_RADIUS: f32 = 0.5;
+const MAX_BRUSH_RADIUS: f32 = 10.;
+const TEST_CRATER_RADIUS: f32 = 60.;
+const TEST_CRATER_STRENGTH: f32 = 3.;
+const CRACKS_PER_CELL: usize = 4;
+const CRACK_COLOR: Color = Color::srgb(0.05, 0.05, 0.05);
+const SAVE_PATH: &str = "map.sav";
+
 pub fn setup_map(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut chunks_pending_rebuild: ResMut<ChunksPendingRebuild>,
     map: Res<Map>,
+    config: Res<TerrainConfig>,
+    coords: Res<TerrainCoords>,
 ) {
-    let terrain = ChunkMap::new(map.points.to_owned(), SQUARE_SIZE);
-    let mesh_handles = terrain.all_chunk_meshes(&mut meshes);
-
-    const CHUNK_LENGTH: f32 = CHUNK_SIZE as f32 * SQUARE_SIZE;
-    let chunk_map_width = terrain.map.len();
-    let chunk_map_height = terrain.map[0].len();
+    info!(
+        "terrain seed: {}, spawn: {:?}, goal: {:?}",
+        map.seed, map.spawn, map.goal
+    );
 
+    let bounds = coords.bounds();
     commands.spawn((
-        Mesh2d(meshes.add(Rectangle::new(
-            (map.width - 2) as f32 * SQUARE_SIZE,
-            (map.height - 2) as f32 * SQUARE_SIZE,
-        ))),
-        MeshMaterial2d(materials.add(WATER_COLOR)),
-        Transform::from_xyz(
-            (map.width - 16) as f32 * SQUARE_SIZE / 2.,
-            (map.height - 16) as f32 * SQUARE_SIZE / 2.,
-            0.,
-        ),
+        Mesh2d(meshes.add(Rectangle::from_size(bounds.size()))),
+        MeshMaterial2d(materials.add(config.tile_color(Tile::Water))),
+        Transform::from_translation(bounds.center().extend(0.)),
     ));
 
-    for x in 0..chunk_map_width {
-        for y in 0..chunk_map_height {
-            commands.spawn((
-                Mesh2d(mesh_handles[x][y].clone()),
-                MeshMaterial2d(materials.add(WALL_COLOR)),
-                Transform::from_translation(Vec3::new(
-                    x as f32 * CHUNK_LENGTH,
-                    y as f32 * CHUNK_LENGTH,
-                    1.,
-                )),
-                TerrainMesh::new(UVec2::new(x as u32, y as u32)),
-            ));
+    let layer_materials = Tile::SOLID.map(|layer| materials.add(config.tile_color(layer)));
+
+    for x in 0..coords.chunks_x {
+        for y in 0..coords.chunks_y {
+            let chunk_position = UVec2::new(x as u32, y as u32);
+            // the meshes start out empty and are filled in once the first
+            // rebuild of every chunk finishes in the background
+            chunks_pending_rebuild.chunks.insert(chunk_position);
+
+            for (index, layer) in Tile::SOLID.into_iter().enumerate() {
+                commands.spawn((
+                    Mesh2d(meshes.add(Chunk::empty_mesh())),
+                    MeshMaterial2d(layer_materials[index].clone()),
+                    Transform::from_translation(
+                        coords
+                            .chunk_origin(chunk_position)
+                            // later layers are drawn on top of earlier ones
+                            .extend(1. + index as f32 * 0.1),
+                    ),
+                    TerrainMesh::new(chunk_position, layer),
+                ));
+            }
         }
     }
 }
 
-pub fn draw_debug_chunk_borders(keyboard: Res<ButtonInput<KeyCode>>, mut gizmos: Gizmos) {
+pub fn spawn_fog(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut fog: ResMut<FogOfWar>,
+    coords: Res<TerrainCoords>,
+) {
+    // the fog's color and opacity come from its vertices
+    let material = materials.add(Color::WHITE);
+
+    for chunk_position in coords.chunks() {
+        fog.chunks_pending.insert(chunk_position);
+
+        commands.spawn((
+            Mesh2d(meshes.add(Chunk::empty_mesh())),
+            MeshMaterial2d(material.clone()),
+            // above the terrain and what is in the cave, below the submarine
+            Transform::from_translation(coords.chunk_origin(chunk_position).extend(4.5)),
+            FogMesh { chunk_position },
+        ));
+    }
+}
+
+/// F switches between hiding, darkening and showing the unexplored cave
+pub fn cycle_fog_mode(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut fog: ResMut<FogOfWar>,
+    coords: Res<TerrainCoords>,
+) {
+    if !keyboard.just_pressed(KeyCode::KeyF) {
+        return;
+    }
+
+    fog.mode = fog.mode.next();
+    fog.chunks_pending.extend(coords.chunks());
+    info!("fog of war: {:?}", fog.mode);
+}
+
+pub fn rebuild_fog(
+    mut q_fog: Query<(&FogMesh, &Mesh2d, &mut Visibility)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut fog: ResMut<FogOfWar>,
+    map: Res<Map>,
+    coords: Res<TerrainCoords>,
+) {
+    if fog.chunks_pending.is_empty() {
+        return;
+    }
+
+    for (fog_mesh, mesh_handle, mut visibility) in q_fog.iter_mut() {
+        if !fog.chunks_pending.contains(&fog_mesh.chunk_position) {
+            continue;
+        }
+
+        if fog.mode == FogMode::Off {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        *visibility = Visibility::Inherited;
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            *mesh = map.build_fog_mesh(&coords, fog_mesh.chunk_position, FOG_COLOR, fog.mode);
+        }
+    }
+
+    fog.chunks_pending.clear();
+}
+
+/// F5 saves the map and what has been explored of it, F9 loads it back
+pub fn save_or_load_map(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mut map: ResMut<Map>,
+    coords: Res<TerrainCoords>,
+    mut chunks_pending_rebuild: ResMut<ChunksPendingRebuild>,
+    (mut dig_progress, mut unsupported_cells): (ResMut<DigProgress>, ResMut<UnsupportedCells>),
+    mut fog: ResMut<FogOfWar>,
+    mut map_loaded: EventWriter<MapLoaded>,
+) {
+    if keyboard.just_pressed(KeyCode::F5) {
+        let saved = File::create(SAVE_PATH).and_then(|file| map.save(&mut BufWriter::new(file)));
+        match saved {
+            Ok(()) => info!("saved the map to {}", SAVE_PATH),
+            Err(error) => error!("could not save the map to {}: {}", SAVE_PATH, error),
+        }
+    }
+
+    if !keyboard.just_pressed(KeyCode::F9) {
+        return;
+    }
+
+    // the chunk entities are laid out for the current size
+    let loaded = File::open(SAVE_PATH)
+        .and_then(|file| Map::load(&mut BufReader::new(file), coords.width, coords.height));
+    let loaded = match loaded {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            error!("could not load the map from {}: {}", SAVE_PATH, error);
+            return;
+        }
+    };
+
+    *map = loaded;
+    dig_progress.cells.clear();
+    unsupported_cells.cells.clear();
+    chunks_pending_rebuild.chunks.extend(coords.chunks());
+    fog.chunks_pending.extend(coords.chunks());
+    map_loaded.write(MapLoaded);
+    info!("loaded the map from {}, seed {}", SAVE_PATH, map.seed);
+}
+
+pub fn draw_debug_chunk_borders(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    coords: Res<TerrainCoords>,
+    mut gizmos: Gizmos,
+) {
     if !keyboard.pressed(KeyCode::Space) {
         return;
     };
 
-    let offset = CHUNK_SIZE as f32 * SQUARE_SIZE / 2. - SQUARE_SIZE;
-    for x in 0..=8 {
-        let x = (x * CHUNK_SIZE) as f32 * SQUARE_SIZE;
-        gizmos.line_2d(
-            Vec2::new(x - offset, -offset),
-            Vec2::new(x - offset, 4. * CHUNK_SIZE as f32 * SQUARE_SIZE - offset),
-            bevy::color::palettes::css::RED,
-        );
+    for x in 0..coords.chunks_x {
+        for y in 0..coords.chunks_y {
+            let bounds = coords.chunk_bounds(UVec2::new(x as u32, y as u32));
+            gizmos.rect_2d(
+                Isometry2d::from_translation(bounds.center()),
+                bounds.size(),
+                bevy::color::palettes::css::RED,
+            );
+        }
     }
-    for y in 0..=4 {
-        let y = (y * CHUNK_SIZE) as f32 * SQUARE_SIZE;
-        gizmos.line_2d(
-            Vec2::new(-offset, y - offset),
-            Vec2::new(8. * CHUNK_SIZE as f32 * SQUARE_SIZE - offset, y - offset),
-            bevy::color::palettes::css::RED,
-        );
+}
+
+/// `[` and `]` resize the brush, `B` switches between a circle and a square
+pub fn update_brush(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut brush: ResMut<TerrainBrush>,
+) {
+    brush.action = if mouse.pressed(brush.remove_button) {
+        Some(BrushMode::Remove)
+    } else if mouse.pressed(brush.add_button) {
+        Some(BrushMode::Add(brush.tile))
+    } else {
+        None
+    };
+
+    if keyboard.just_pressed(KeyCode::BracketLeft) {
+        brush.radius = (brush.radius - 0.5).max(MIN_BRUSH_RADIUS);
+    }
+    if keyboard.just_pressed(KeyCode::BracketRight) {
+        brush.radius = (brush.radius + 0.5).min(MAX_BRUSH_RADIUS);
+    }
+    if keyboard.just_pressed(KeyCode::KeyB) {
+        brush.shape = match brush.shape {
+            BrushShape::Circle => BrushShape::Square,
+            BrushShape::Square => BrushShape::Circle,
+        };
     }
 }
 
 pub fn draw_on_map(
     q_camera: Query<(&Camera, &GlobalTransform)>,
     q_window: Query<&Window>,
-    mut chunks_pending_rebuild: ResMut<ChunksPendingRebuild>,
-    mut map: ResMut<Map>,
+    mut brush: ResMut<TerrainBrush>,
+    mut editor: TerrainEditor,
+    coords: Res<TerrainCoords>,
 ) {
-    let Ok((camera, camera_pos)) = q_camera.single() else {
+    let Some(mode) = brush.action else {
+        brush.last_stamp = None;
         return;
     };
 
-    let Ok(window) = q_window.single() else {
+    let Some(cursor_pos) = cursor_world_position(&q_camera, &q_window) else {
+        brush.last_stamp = None;
         return;
     };
 
-    let Some(cursor_pos) = window
-        .cursor_position()
-        .and_then(|cursor_pos| camera.viewport_to_world_2d(camera_pos, cursor_pos).ok())
-    else {
+    if coords.world_to_cell(cursor_pos).is_none() {
+        brush.last_stamp = None;
         return;
-    };
+    }
+
+    // stamp along the whole way from the last position, so moving the
+    // cursor quickly still leaves an unbroken stroke
+    let cursor = coords.world_to_cell_position(cursor_pos);
+    let start = brush.last_stamp.unwrap_or(cursor);
+    brush.last_stamp = Some(cursor);
+
+    let steps = ((cursor - start).length() / STROKE_SPACING).ceil().max(1.) as usize;
+    for step in 1..=steps {
+        let center = start.lerp(cursor, step as f32 / steps as f32);
+        editor.stamp_brush(
+            center,
+            brush.radius,
+            brush.shape,
+            mode,
+            TerrainChangeCause::Editor,
+        );
+    }
+}
+
+/// Middle click sets off a test explosion under the cursor
+pub fn explode_at_cursor(
+    q_camera: Query<(&Camera, &GlobalTransform)>,
+    q_window: Query<&Window>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    mut craters: EventWriter<Crater>,
+) {
+    if !mouse.just_pressed(MouseButton::Middle) {
+        return;
+    }
 
-    let Some((cursor_x, cursor_y)) = map.world_space_to_index(cursor_pos) else {
+    let Some(cursor_pos) = cursor_world_position(&q_camera, &q_window) else {
         return;
     };
-    map.points[cursor_x][cursor_y] = false;
 
-    let chunk_index = UVec2::new((cursor_x - 1) as u32 / 16, (cursor_y - 1) as u32 / 16);
-    chunks_pending_rebuild.chunks.push(chunk_index);
+    craters.write(Crater {
+        center: cursor_pos,
+        radius: TEST_CRATER_RADIUS,
+        strength: TEST_CRATER_STRENGTH,
+        falloff: 0.5,
+    });
+}
+
+pub fn carve_craters(mut craters: EventReader<Crater>, mut editor: TerrainEditor) {
+    for crater in craters.read() {
+        editor.carve_crater(crater, TerrainChangeCause::Explosion);
+    }
+}
+
+pub fn collapse_loose_tiles(mut editor: TerrainEditor) {
+    editor.collapse_loose_tiles();
+}
+
+/// Cracks over partly mined cells, more of them the closer a cell is to breaking
+pub fn draw_dig_cracks(
+    dig_progress: Res<DigProgress>,
+    coords: Res<TerrainCoords>,
+    mut gizmos: Gizmos,
+) {
+    for (&(x, y), &progress) in dig_progress.cells.iter() {
+        let center = coords.cell_to_world((x, y));
+        let length = progress * coords.square_size * 0.6;
 
-    // if we are on the edge of a chunk, then the neighbor must be updated
-    if cursor_x % 16 == 1 {
-        chunks_pending_rebuild
-            .chunks
-            .push(UVec2::new(chunk_index.x.saturating_sub(1), chunk_index.y));
+        for crack in 0..(progress * CRACKS_PER_CELL as f32).ceil() as usize {
+            // the same cell always cracks the same way
+            let hash = x.wrapping_mul(73_856_093)
+                ^ y.wrapping_mul(19_349_663)
+                ^ crack.wrapping_mul(83_492_791);
+            let direction = Vec2::from_angle((hash % 360) as f32 * PI / 180.);
+            gizmos.line_2d(center, center + direction * length, CRACK_COLOR);
+        }
     }
-    if cursor_y % 16 == 1 {
-        chunks_pending_rebuild
-            .chunks
-            .push(UVec2::new(chunk_index.x, chunk_index.y.saturating_sub(1)));
+}
+
+/// Summarises every edit of the terrain at the debug log level
+pub fn log_terrain_changes(mut terrain_modified: EventReader<TerrainModified>) {
+    for event in terrain_modified.read() {
+        let removed = event
+            .changes
+            .iter()
+            .filter(|change| change.previous.is_solid() && !change.new.is_solid())
+            .count();
+        let added = event
+            .changes
+            .iter()
+            .filter(|change| !change.previous.is_solid() && change.new.is_solid())
+            .count();
+
+        debug!(
+            "{:?} changed {} cells, {} dug out and {} filled in",
+            event.cause,
+            event.changes.len(),
+            removed,
+            added
+        );
     }
 }
 
 pub fn regenerate_chunks(
-    mut commands: Commands,
-    q_chunks: Query<(Entity, &TerrainMesh)>,
-    mut meshes: ResMut<Assets<Mesh>>,
+    mut chunk_mesh_tasks: ResMut<ChunkMeshTasks>,
     mut chunks_pending_rebuild: ResMut<ChunksPendingRebuild>,
     map: Res<Map>,
+    coords: Res<TerrainCoords>,
 ) {
-    if chunks_pending_rebuild.chunks.is_empty() {
-        return;
+    let task_pool = AsyncComputeTaskPool::get();
+    let square_size = coords.square_size;
+
+    for chunk_position in chunks_pending_rebuild.chunks.drain() {
+        let (x, y) = (chunk_position.x as usize, chunk_position.y as usize);
+        if x >= coords.chunks_x || y >= coords.chunks_y {
+            continue;
+        }
+
+        let chunk = Chunk::new(&map, x, y);
+        let chunk_origin = coords.chunk_origin(chunk_position);
+        let task = task_pool.spawn(async move {
+            return ChunkBuild {
+                meshes: Tile::SOLID.map(|layer| (layer, chunk.build_mesh(square_size, layer))),
+                contours: chunk
+                    .generate_contours(square_size)
+                    .into_iter()
+                    .map(|segment| segment.map(|point| point + chunk_origin))
+                    .collect(),
+            };
+        });
+
+        // a build still running for an earlier edit is now outdated
+        let generation = chunk_mesh_tasks
+            .generations
+            .entry(chunk_position)
+            .or_default();
+        *generation += 1;
+        let generation = *generation;
+        chunk_mesh_tasks
+            .tasks
+            .push((chunk_position, generation, task));
     }
+}
 
-    let terrain = ChunkMap::new(map.points.to_owned(), SQUARE_SIZE);
+pub fn apply_chunk_meshes(
+    q_chunks: Query<(&TerrainMesh, &Mesh2d)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut chunk_mesh_tasks: ResMut<ChunkMeshTasks>,
+    mut contours: ResMut<TerrainContours>,
+) {
+    let mut finished = HashMap::new();
+
+    let ChunkMeshTasks { tasks, generations } = &mut *chunk_mesh_tasks;
+    tasks.retain_mut(|(chunk_position, generation, task)| {
+        let Some(build) = block_on(poll_once(task)) else {
+            return true;
+        };
+        if generations.get(chunk_position) != Some(generation) {
+            return false;
+        }
 
-    let mesh_handles = terrain.all_chunk_meshes(&mut meshes);
+        contours.set_chunk(*chunk_position, build.contours);
+        finished.insert(*chunk_position, Vec::from(build.meshes));
+        return false;
+    });
 
-    for (entity, terrain_mesh) in q_chunks.iter() {
-        let x = terrain_mesh.chunk_position.x as usize;
-        let y = terrain_mesh.chunk_position.y as usize;
+    if finished.is_empty() {
+        return;
+    }
+
+    for (terrain_mesh, mesh_handle) in q_chunks.iter() {
+        let Some(layer_meshes) = finished.get_mut(&terrain_mesh.chunk_position) else {
+            continue;
+        };
 
-        if !chunks_pending_rebuild
-            .chunks
-            .contains(&terrain_mesh.chunk_position)
-        {
+        let Some(index) = layer_meshes
+            .iter()
+            .position(|(layer, _)| *layer == terrain_mesh.layer)
+        else {
             continue;
+        };
+
+        let (_, new_mesh) = layer_meshes.swap_remove(index);
+        if let Some(mesh) = meshes.get_mut(&mesh_handle.0) {
+            *mesh = new_mesh;
         }
+    }
+}
 
-        commands
-            .entity(entity)
-            .insert(Mesh2d(mesh_handles[x][y].clone()));
+pub fn stitch_terrain_contours(mut contours: ResMut<TerrainContours>, config: Res<TerrainConfig>) {
+    if !contours.needs_stitching {
+        return;
     }
 
-    chunks_pending_rebuild.chunks.clear();
+    contours.stitch(config.square_size);
+}
+
+pub fn draw_debug_contours(
+    keyboard: Res<ButtonInput<KeyCode>>,
+    contours: Res<TerrainContours>,
+    mut gizmos: Gizmos,
+) {
+    if !keyboard.pressed(KeyCode::KeyC) {
+        return;
+    };
+
+    for polyline in contours.polylines.iter() {
+        gizmos.linestrip_2d(polyline.iter().copied(), bevy::color::palettes::css::YELLOW);
+    }
+}
+
+fn cursor_world_position(
+    q_camera: &Query<(&Camera, &GlobalTransform)>,
+    q_window: &Query<&Window>,
+) -> Option<Vec2> {
+    let (camera, camera_pos) = q_camera.single().ok()?;
+    let window = q_window.single().ok()?;
+
+    return window
+        .cursor_position()
+        .and_then(|cursor_pos| camera.viewport_to_world_2d(camera_pos, cursor_pos).ok());
 }
@@ -0,0 +1,83 @@
+use bevy::color::Color;
+
+use super::{BEDROCK_COLOR, HARD_ROCK_COLOR, ORE_COLOR, SAND_COLOR, WALL_COLOR, WATER_COLOR};
+
+/// The material at a single point of the map.
+///
+/// Solid tiles are ordered so that each one is meshed on top of the ones
+/// before it, see [`Chunk::generate_vertices`](super::chunk::Chunk::generate_vertices)
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub enum Tile {
+    #[default]
+    Water,
+    Sand,
+    Rock,
+    HardRock,
+    Ore,
+    /// The indestructible map border
+    Bedrock,
+}
+
+impl Tile {
+    pub const ALL: [Tile; 6] = [
+        Tile::Water,
+        Tile::Sand,
+        Tile::Rock,
+        Tile::HardRock,
+        Tile::Ore,
+        Tile::Bedrock,
+    ];
+
+    pub const SOLID: [Tile; 5] = [
+        Tile::Sand,
+        Tile::Rock,
+        Tile::HardRock,
+        Tile::Ore,
+        Tile::Bedrock,
+    ];
+
+    pub fn is_solid(self) -> bool {
+        return self != Tile::Water;
+    }
+
+    /// Whether the tile falls into water that opens up beneath it, see
+    /// [`Map::collapse_loose_tiles`](super::resources::Map::collapse_loose_tiles)
+    pub fn is_loose(self) -> bool {
+        return self == Tile::Sand;
+    }
+
+    /// Seconds of mining at a dig power of 1 it takes to break the tile,
+    /// `None` for tiles that can't be mined
+    pub fn hardness(self) -> Option<f32> {
+        return match self {
+            Tile::Water | Tile::Bedrock => None,
+            Tile::Sand => Some(0.3),
+            Tile::Rock => Some(1.),
+            Tile::HardRock => Some(2.5),
+            Tile::Ore => Some(1.5),
+        };
+    }
+
+    /// How much damage running into the tile does compared to rock
+    pub fn impact_damage_scale(self) -> f32 {
+        return match self {
+            Tile::Water => 0.,
+            Tile::Sand => 0.4,
+            Tile::Rock => 1.,
+            Tile::HardRock => 1.5,
+            Tile::Ore => 1.2,
+            Tile::Bedrock => 2.,
+        };
+    }
+
+    pub fn default_color(self) -> Color {
+        return match self {
+            Tile::Water => WATER_COLOR,
+            Tile::Sand => SAND_COLOR,
+            Tile::Rock => WALL_COLOR,
+            Tile::HardRock => HARD_ROCK_COLOR,
+            Tile::Ore => ORE_COLOR,
+            Tile::Bedrock => BEDROCK_COLOR,
+        };
+    }
+}
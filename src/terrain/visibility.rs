@@ -0,0 +1,189 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::math::Vec2;
+
+use super::resources::Map;
+
+/// Rays cast around the edge of a visibility polygon where no wall is in
+/// the way, more make the edge rounder
+const ARC_STEPS: usize = 64;
+/// Angle the rays next to a wall's end are turned by, so one stops at the
+/// wall and the other carries on past it
+const CORNER_OFFSET: f32 = 0.0001;
+
+/// The part of a circle a light or a viewer covers
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ViewCone {
+    pub direction: Vec2,
+    /// Radians on either side of `direction`
+    pub half_angle: f32,
+}
+
+/// Everything visible from `origin` within `radius` when `segments` block
+/// the view, as the points around its outline in counterclockwise order.
+/// Without a cone they go all the way around and the polygon closes from
+/// the last point back to the first, with one it is the fan from `origin`
+/// through the points. Segments block the view from both sides, and any
+/// unit works as long as it is the same for every argument
+pub fn visibility_polygon(
+    origin: Vec2,
+    radius: f32,
+    segments: &[[Vec2; 2]],
+    cone: Option<ViewCone>,
+) -> Vec<Vec2> {
+    let (start, span) = match cone {
+        Some(cone) => (
+            cone.direction.to_angle() - cone.half_angle,
+            (cone.half_angle * 2.).min(TAU),
+        ),
+        None => (-PI, TAU),
+    };
+    let closed = cone.is_none();
+
+    // only the ends of walls can change what a ray hits, the arc steps
+    // round off the edge of the light in between
+    let arc_steps = if closed { ARC_STEPS } else { ARC_STEPS + 1 };
+    let mut offsets: Vec<f32> = (0..arc_steps)
+        .map(|step| span * step as f32 / ARC_STEPS as f32)
+        .collect();
+
+    for point in segments.iter().flatten() {
+        let to_point = *point - origin;
+        if to_point.length_squared() > radius * radius {
+            continue;
+        }
+
+        let angle = to_point.to_angle();
+        for turn in [-CORNER_OFFSET, 0., CORNER_OFFSET] {
+            let offset = (angle + turn - start).rem_euclid(TAU);
+            if offset <= span {
+                offsets.push(offset);
+            }
+        }
+    }
+
+    offsets.sort_by(|a, b| a.total_cmp(b));
+    offsets.dedup_by(|a, b| (*a - *b).abs() < CORNER_OFFSET / 2.);
+
+    return offsets
+        .into_iter()
+        .map(|offset| {
+            let direction = Vec2::from_angle(start + offset);
+            let distance = segments
+                .iter()
+                .filter_map(|segment| ray_distance(origin, direction, *segment))
+                .fold(radius, f32::min);
+            return origin + direction * distance;
+        })
+        .collect();
+}
+
+/// Distance along a normalized ray to where it crosses a segment
+fn ray_distance(origin: Vec2, direction: Vec2, [a, b]: [Vec2; 2]) -> Option<f32> {
+    let edge = b - a;
+    let denominator = direction.perp_dot(edge);
+    if denominator.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    let to_start = a - origin;
+    let distance = to_start.perp_dot(edge) / denominator;
+    let along = to_start.perp_dot(direction) / denominator;
+    if distance < 0. || !(0. ..=1.).contains(&along) {
+        return None;
+    }
+
+    return Some(distance);
+}
+
+impl Map {
+    /// [`visibility_polygon`] against the contour of the map, measured in
+    /// cells
+    pub fn visibility_polygon(
+        &self,
+        origin: Vec2,
+        radius: f32,
+        cone: Option<ViewCone>,
+    ) -> Vec<Vec2> {
+        let segments = self.contour_segments_in(origin - radius, origin + radius);
+        return visibility_polygon(origin, radius, &segments, cone);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f32 = 5.;
+
+    /// The outline point closest to `angle`
+    fn point_towards(polygon: &[Vec2], angle: f32) -> Vec2 {
+        return *polygon
+            .iter()
+            .min_by(|a, b| {
+                let difference = |point: Vec2| Vec2::from_angle(angle).angle_to(point).abs();
+                return difference(**a).total_cmp(&difference(**b));
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn nothing_in_the_way_is_a_circle() {
+        let polygon = visibility_polygon(Vec2::ONE, RADIUS, &[], None);
+
+        assert_eq!(polygon.len(), ARC_STEPS);
+        for point in polygon {
+            assert!((point.distance(Vec2::ONE) - RADIUS).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn wall_in_front_cuts_the_polygon() {
+        let wall = [Vec2::new(2., -1.), Vec2::new(2., 1.)];
+        let polygon = visibility_polygon(Vec2::ZERO, RADIUS, &[wall], None);
+
+        assert!((point_towards(&polygon, 0.) - Vec2::new(2., 0.)).length() < 1e-4);
+        assert!((point_towards(&polygon, PI).length() - RADIUS).abs() < 1e-4);
+        // every ray aimed at the wall stops on it
+        let wall_half_angle = 0.5_f32.atan();
+        for point in polygon {
+            if point.to_angle().abs() < wall_half_angle - CORNER_OFFSET {
+                assert!((point.x - 2.).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn corner_casts_a_shadow_edge() {
+        let corner = Vec2::new(2., 0.);
+        let wall = [corner, Vec2::new(2., 2.)];
+        let polygon = visibility_polygon(Vec2::ZERO, RADIUS, &[wall], None);
+
+        // one ray stops at the corner and the one next to it carries on
+        // past it to the edge of the light
+        let at_corner = polygon
+            .iter()
+            .position(|point| point.distance(corner) < 1e-3);
+        let at_corner = at_corner.expect("no ray stops at the corner");
+        let past_corner = polygon[at_corner - 1];
+
+        assert!((past_corner.length() - RADIUS).abs() < 1e-4);
+        assert!(past_corner.to_angle().abs() < CORNER_OFFSET * 2.);
+    }
+
+    #[test]
+    fn cone_stays_within_its_angle() {
+        let cone = ViewCone {
+            direction: Vec2::Y,
+            half_angle: 0.4,
+        };
+        let polygon = visibility_polygon(Vec2::ZERO, RADIUS, &[], Some(cone));
+
+        assert_eq!(polygon.len(), ARC_STEPS + 1);
+        for point in polygon.iter() {
+            assert!(Vec2::Y.angle_to(*point).abs() <= cone.half_angle + 1e-4);
+        }
+        assert!((Vec2::Y.angle_to(polygon[0]) + cone.half_angle).abs() < 1e-4);
+        assert!((Vec2::Y.angle_to(polygon[ARC_STEPS]) - cone.half_angle).abs() < 1e-4);
+    }
+}